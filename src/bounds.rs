@@ -0,0 +1,58 @@
+use crate::packet::RayPacket;
+use crate::point::Point3;
+use crate::ray::Ray;
+use crate::real::Real;
+use crate::simd::Simd;
+
+
+/* The smallest axis aligned box around some points, given by its lowest and highest corner. None without points. */
+pub fn around<I: IntoIterator<Item = Point3>>(points: I) -> Option<(Point3, Point3)> {
+    let mut points = points.into_iter();
+    let first = points.next()?;
+    Some(points.fold((first, first), |(min, max), p| (
+        Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+        Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z))
+    )))
+}
+
+pub fn corners(min: &Point3, max: &Point3) -> [Point3; 8] {
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|i| Point3::new(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z }
+    ))
+}
+
+/* Slab test of a ray against an axis aligned box. */
+pub fn hits_box(ray: &Ray, min: &Point3, max: &Point3) -> bool {
+    let mut near = Real::NEG_INFINITY;
+    let mut far = Real::INFINITY;
+    for (origin, direction, low, high) in [
+        (ray.origin.x, ray.direction.x, min.x, max.x),
+        (ray.origin.y, ray.direction.y, min.y, max.y),
+        (ray.origin.z, ray.direction.z, min.z, max.z)
+    ] {
+        let t0 = (low - origin) / direction;
+        let t1 = (high - origin) / direction;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    near <= far && far >= 0.0
+}
+
+/* The slab test for every ray of a packet at once, bit i of the result is set when the ray in lane i hits the box. */
+pub fn hits_box_packet<L: Simd>(packet: &RayPacket<L>, min: &Point3, max: &Point3) -> u32 {
+    let mut near = L::splat(Real::NEG_INFINITY);
+    let mut far = L::splat(Real::INFINITY);
+    for (origin, direction, low, high) in [
+        (packet.origin.x, packet.direction.x, min.x, max.x),
+        (packet.origin.y, packet.direction.y, min.y, max.y),
+        (packet.origin.z, packet.direction.z, min.z, max.z)
+    ] {
+        let t0 = (L::splat(low) - origin) / direction;
+        let t1 = (L::splat(high) - origin) / direction;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    !(far.lt(near) | far.lt(L::splat(0.0))) & packet.active & L::all()
+}
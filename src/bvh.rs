@@ -0,0 +1,222 @@
+use std::ops;
+
+use crate::bounds::{around, hits_box, hits_box_packet};
+use crate::packet::RayPacket;
+use crate::point::Point3;
+use crate::ray::Ray;
+use crate::traits::Intersectable;
+
+
+// The most objects a leaf holds before it is split in two.
+const LEAF_SIZE: usize = 2;
+// Splitting at the median keeps the tree balanced, so it is no deeper
+// than the number of bits in the object count.
+const MAX_DEPTH: usize = usize::BITS as usize;
+
+struct Node {
+    // A box around everything below the node. A leaf holds the objects
+    // order[start..start + count], an inner node has a count of zero,
+    // its first child right after it and its second child at start.
+    min: Point3,
+    max: Point3,
+    start: usize,
+    count: usize
+}
+
+pub struct Bvh {
+    // The objects of a scene together with a tree of boxes around them,
+    // so a ray only tests the objects in the boxes it passes through.
+    // Objects without a box, like planes, are tested by every ray. The
+    // objects keep their place in the list, hits refer to them by index.
+    objects: Vec<Box<dyn Intersectable>>,
+    nodes: Vec<Node>,
+    order: Vec<usize>,
+    unbounded: Vec<usize>
+}
+
+/* Build the nodes for a part of the objects, each given with its box. Returns the index of the top node. The
+objects are split at the median of their centers, along the axis where the centers lie furthest apart. */
+fn build(nodes: &mut Vec<Node>, items: &mut [(usize, Point3, Point3)], start: usize) -> usize {
+    let (min, max) = around(items.iter().flat_map(|(_, min, max)| [*min, *max])).expect("a node holds objects");
+    let index = nodes.len();
+    nodes.push(Node { min, max, start, count: items.len() });
+    if items.len() <= LEAF_SIZE {
+        return index;
+    }
+
+    let center = |(_, min, max): &(usize, Point3, Point3)| Point3::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0, (min.z + max.z) / 2.0);
+    let (low, high) = around(items.iter().map(center)).expect("a node holds objects");
+    let spread = high - low;
+    let axis = |p: Point3| if spread.x >= spread.y && spread.x >= spread.z {
+        p.x
+    } else if spread.y >= spread.z {
+        p.y
+    } else {
+        p.z
+    };
+    let middle = items.len() / 2;
+    items.select_nth_unstable_by(middle, |a, b| axis(center(a)).total_cmp(&axis(center(b))));
+
+    let (first, second) = items.split_at_mut(middle);
+    build(nodes, first, start);
+    let second = build(nodes, second, start + middle);
+    nodes[index].start = second;
+    nodes[index].count = 0;
+    index
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Box<dyn Intersectable>>) -> Self {
+        let (mut bounded, mut unbounded) = (Vec::new(), Vec::new());
+        for (i, object) in objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some((min, max)) => bounded.push((i, min, max)),
+                None => unbounded.push(i)
+            }
+        }
+        let mut nodes = Vec::new();
+        if !bounded.is_empty() {
+            build(&mut nodes, &mut bounded, 0);
+        }
+        let order = bounded.into_iter().map(|(i, _, _)| i).collect();
+        Self { objects, nodes, order, unbounded }
+    }
+
+    /* The number of boxes in the tree, the most box tests a ray can take. */
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /* Call visit with the index of every object the ray may hit: those in the boxes it passes through and those
+    without a box. Returns the number of boxes the ray was tested against. */
+    pub fn visit<F: FnMut(usize)>(&self, ray: &Ray, mut visit: F) -> u32 {
+        let mut tests = 0;
+        let mut stack = [0; MAX_DEPTH];
+        let mut depth = usize::from(!self.nodes.is_empty());
+        while depth > 0 {
+            depth -= 1;
+            let node = &self.nodes[stack[depth]];
+            tests += 1;
+            if !hits_box(ray, &node.min, &node.max) {
+                continue;
+            }
+            if node.count > 0 {
+                self.order[node.start..node.start + node.count].iter().for_each(|&i| visit(i));
+                continue;
+            }
+            let first = stack[depth] + 1;
+            stack[depth] = node.start;
+            stack[depth + 1] = first;
+            depth += 2;
+        }
+        self.unbounded.iter().for_each(|&i| visit(i));
+        tests
+    }
+
+    /* The same for all rays of a packet at once. Every box is tested with the SIMD slab test for the rays that made
+    it into its parent, visit is given the mask of the lanes that reached the object. Returns the number of boxes the
+    packet was tested against. */
+    pub fn visit_packet<F: FnMut(usize, u32)>(&self, packet: &RayPacket, mut visit: F) -> u32 {
+        let mut tests = 0;
+        let mut stack = [(0, 0); MAX_DEPTH];
+        let mut depth = 0;
+        if !self.nodes.is_empty() {
+            stack[0] = (0, packet.active);
+            depth = 1;
+        }
+        while depth > 0 {
+            depth -= 1;
+            let (index, mask) = stack[depth];
+            let node = &self.nodes[index];
+            tests += 1;
+            let mask = mask & hits_box_packet(&RayPacket { active: mask, ..*packet }, &node.min, &node.max);
+            if mask == 0 {
+                continue;
+            }
+            if node.count > 0 {
+                self.order[node.start..node.start + node.count].iter().for_each(|&i| visit(i, mask));
+                continue;
+            }
+            stack[depth] = (node.start, mask);
+            stack[depth + 1] = (index + 1, mask);
+            depth += 2;
+        }
+        self.unbounded.iter().for_each(|&i| visit(i, packet.active));
+        tests
+    }
+}
+
+impl ops::Deref for Bvh {
+    type Target = [Box<dyn Intersectable>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.objects
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::color::RGBAColor;
+    use crate::material::Surface;
+    use crate::normal::Normal3;
+    use crate::plane::Plane;
+    use crate::real::Real;
+    use crate::simd::{Lanes, Simd};
+    use crate::sphere::Sphere;
+    use crate::vec::Vec3;
+
+    fn row_of_spheres(count: usize) -> Vec<Box<dyn Intersectable>> {
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
+        (0..count).map(|i| {
+            let sphere: Box<dyn Intersectable> = Box::new(Sphere {
+                origin: Point3::new(3.0 * i as Real, 0.0, -5.0), radius: 1.0, color: Some(color), material: None, surface: Surface::Color(color)
+            });
+            sphere
+        }).collect()
+    }
+
+    #[test]
+    fn test_rays_only_visit_the_objects_in_their_boxes() {
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
+        let mut objects = row_of_spheres(16);
+        objects.push(Box::new(Plane {
+            origin: Point3::new(0.0, -1.0, 0.0), orient: Normal3::new(0.0, 1.0, 0.0),
+            color: Some(color), material: None, one_sided: false, surface: Surface::Color(color)
+        }));
+        let bvh = Bvh::new(objects);
+        assert_eq!(bvh.len(), 17);
+
+        // Straight at the seventh sphere, which shares a leaf with the
+        // eighth. The plane has no box and is always visited.
+        let ray = Ray::new(Point3::new(18.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut visited = Vec::new();
+        let tests = bvh.visit(&ray, |i| visited.push(i));
+        visited.sort();
+        assert_eq!(visited, vec![6, 7, 16]);
+        assert!((tests as usize) < bvh.node_count());
+
+        // Every object is found by a ray that passes along the whole row
+        let along = Ray::new(Point3::new(-10.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut visited = Vec::new();
+        assert_eq!(bvh.visit(&along, |i| visited.push(i)) as usize, bvh.node_count());
+        visited.sort();
+        assert_eq!(visited, (0..17).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_packets_visit_objects_with_the_lanes_that_reach_them() {
+        let bvh = Bvh::new(row_of_spheres(16));
+        let rays: Vec<Ray> = (0..Lanes::WIDTH)
+            .map(|i| Ray::new(Point3::new(3.0 * i as Real, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)))
+            .collect();
+        let mut visited = Vec::new();
+        bvh.visit_packet(&RayPacket::new(&rays), |i, mask| visited.push((i, mask)));
+        visited.sort();
+        // Every lane hits the sphere in front of it, but reaches both
+        // spheres of the leaf that holds it.
+        assert_eq!(visited, (0..Lanes::WIDTH).map(|i| (i, 0b11 << (i & !1))).collect::<Vec<(usize, u32)>>());
+    }
+}
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+
+use crate::point::Point3;
+use crate::ray::Ray;
+use crate::quaternion::Quaternion;
+use crate::real::Real;
+use crate::transform::Transform;
+use crate::vec::Vec3;
+
+
+pub struct Window {
+    // The window represents the screen that shows all pixels.
+    // It is created by providing a width. The aspect ratio is
+    // used to calculate a proper height
+    pub width: i32,
+    pub height: i32
+}
+
+impl Window {
+    pub fn new(width: i32) -> Self {
+        // aspect ratio 16/9 (width to height)
+        let aspect_ratio: Real = 16.0 / 9.0;
+
+        let height = (width as Real / aspect_ratio) as i32;
+        assert!(height > 1);
+        Self { width, height }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(try_from = "CameraFile")]
+pub struct Camera {
+    // The camera is the location from which the rays are shot.
+    // Each ray shot through the viewport originates at the camera
+    // origin. The orientation is described by a yaw around the y axis
+    // and a pitch up or down, both in radians. With both at zero the
+    // camera looks down the negative z axis.
+    pub location: Point3,
+    pub yaw: Real,
+    pub pitch: Real,
+    // vertical field of view in degrees
+    pub vfov: Real
+}
+
+fn default_vfov() -> Real {
+    90.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraFile {
+    // How a camera is written in a scene file. Instead of a yaw and
+    // pitch it can be given a point to look at, which is turned into
+    // a yaw and pitch when the scene is read.
+    location: Point3,
+    #[serde(default)]
+    yaw: Option<Real>,
+    #[serde(default)]
+    pitch: Option<Real>,
+    #[serde(default = "default_vfov")]
+    vfov: Real,
+    #[serde(default)]
+    look_at: Option<Point3>
+}
+
+impl TryFrom<CameraFile> for Camera {
+    type Error = String;
+
+    fn try_from(file: CameraFile) -> Result<Self, Self::Error> {
+        let mut camera = Self { location: file.location, yaw: file.yaw.unwrap_or(0.0), pitch: file.pitch.unwrap_or(0.0), vfov: file.vfov };
+        match (file.look_at, file.yaw.or(file.pitch)) {
+            (None, _) => Ok(camera),
+            (Some(_), Some(_)) => Err("a camera either has a look_at point or a yaw and pitch, not both".to_string()),
+            (Some(target), None) if camera.look_at(target) => Ok(camera),
+            (Some(_), None) => Err("look_at must not be the camera location or straight above or below it".to_string())
+        }
+    }
+}
+
+impl Camera {
+    pub fn new(location: Point3) -> Self {
+        Self { location, yaw: 0.0, pitch: 0.0, vfov: default_vfov() }
+    }
+
+    /* Rotation from camera space, where the camera looks down the negative z axis with y up, to the world. */
+    pub fn orientation(&self) -> Quaternion {
+        Quaternion::from_euler(self.yaw, self.pitch, 0.0)
+    }
+
+    /* Turn the camera to look in a direction. The camera does not roll, so only the yaw and pitch are kept. */
+    pub fn look_along(&mut self, direction: Vec3) {
+        let direction = direction.normalise();
+        self.yaw = direction.x.atan2(-direction.z);
+        self.pitch = direction.y.clamp(-1.0, 1.0).asin();
+    }
+
+    /* Turn the camera towards a point. The orientation is that of a look-at transform with the world y axis as up.
+    Returns false and leaves the camera as it is when the point is the location or straight above or below it. */
+    pub fn look_at(&mut self, target: Point3) -> bool {
+        let Some(frame) = Transform::look_at(self.location, target, Vec3::new(0.0, 1.0, 0.0)) else {
+            return false;
+        };
+        self.look_along(Quaternion::from_mat4(frame.matrix()).rotate(&Vec3::new(0.0, 0.0, -1.0)));
+        true
+    }
+
+    /* Unit vector pointing in the direction the camera is looking */
+    pub fn forward(&self) -> Vec3 {
+        self.orientation().rotate(&Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    /* Unit vector pointing to the right of the camera, always horizontal */
+    pub fn right(&self) -> Vec3 {
+        self.orientation().rotate(&Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    /* Unit vector pointing up from the camera, perpendicular to forward and right */
+    pub fn up(&self) -> Vec3 {
+        self.orientation().rotate(&Vec3::new(0.0, 1.0, 0.0))
+    }
+}
+
+
+pub struct Viewport {
+    // The viewport described the small window through which
+    // the rays are shot into the world. This is a 2D plane in
+    // front of the camera.
+    pub starting_pixel: Point3,
+    pub pixel_delta_u: Vec3,
+    pub pixel_delta_v: Vec3
+}
+
+impl Viewport {
+    pub fn new(window: &Window, camera: &Camera) -> Self {
+        // The viewport is a 2D rectangle in front of the camera where
+        // we are shooting our rays through. Its important that we define
+        // its height and width using our choosen aspect ratio.
+        let focal_length = 1.0;     // focal length is the length from origin to the viewport
+        let viewport_height = 2.0 * (camera.vfov.to_radians() / 2.0).tan() * focal_length;
+        let viewport_width = viewport_height * (window.width as Real / window.height as Real);
+
+        // We need two vectors across horizontal and down the vertical viewport edges.
+        // They follow the orientation of the camera.
+        let viewport_u = viewport_width * camera.right();
+        let viewport_v = viewport_height * -camera.up();
+
+        // We also need two vectors that define that span the distance between two pixels.
+        let pixel_delta_u = viewport_u / window.width as Real;
+        let pixel_delta_v = viewport_v / window.height as Real;
+
+        // Get a vector to the upper left pixel by using the focal lenght and our viewport vectors
+        // then use our delta pixels to get the exact location of the pixel itself.
+        let viewport_upper_left = camera.location + focal_length * camera.forward() - (viewport_u/2.0) - (viewport_v / 2.0);
+        let starting_pixel = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+        Self { starting_pixel, pixel_delta_u, pixel_delta_v }
+    }
+
+    /* Shoot a ray from the camera through pixel (x, y). The offsets are relative
+    to the pixel center and should lie in [-0.5, 0.5) to stay inside the pixel. */
+    pub fn get_ray(&self, camera: &Camera, x: i32, y: i32, offset_x: Real, offset_y: Real) -> Ray {
+        let pixel_center = self.starting_pixel
+            + ((x as Real + offset_x) * self.pixel_delta_u)
+            + ((y as Real + offset_y) * self.pixel_delta_v);
+        let ray_direction = pixel_center - camera.location;
+        Ray::new(camera.location, ray_direction)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::real::TOLERANCE;
+
+    #[test]
+    fn test_camera_basis_is_orthonormal() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0));
+        camera.yaw = 0.7;
+        camera.pitch = -0.4;
+        let (f, r, u) = (camera.forward(), camera.right(), camera.up());
+
+        for v in [f, r, u] {
+            assert!((v.magnitude() - 1.0).abs() < TOLERANCE);
+        }
+        assert!(f.dot(&r).abs() < TOLERANCE);
+        assert!(f.dot(&u).abs() < TOLERANCE);
+        assert!(r.dot(&u).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn test_center_ray_follows_camera() {
+        let window = Window::new(64);
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0));
+        camera.yaw = crate::real::consts::FRAC_PI_2;
+        let viewport = Viewport::new(&window, &camera);
+
+        // looking a quarter turn to the right means looking down the positive x axis
+        let ray = viewport.get_ray(&camera, window.width / 2, window.height / 2, -0.5, -0.5);
+        assert!((ray.direction.x - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_look_at_sets_yaw_and_pitch() {
+        let camera: Camera = serde_json::from_str(r#"{ "location": { "x": 0.0, "y": 0.0, "z": 0.0 }, "look_at": { "x": 1.0, "y": 1.0, "z": 0.0 } }"#).unwrap();
+        assert!((camera.yaw - crate::real::consts::FRAC_PI_2).abs() < TOLERANCE);
+        assert!((camera.pitch - crate::real::consts::FRAC_PI_4).abs() < TOLERANCE);
+        assert!((camera.forward() - Vec3::new(1.0, 1.0, 0.0).normalise()).magnitude() < TOLERANCE);
+
+        let both = r#"{ "location": { "x": 0.0, "y": 0.0, "z": 0.0 }, "yaw": 0.5, "look_at": { "x": 1.0, "y": 0.0, "z": 0.0 } }"#;
+        assert!(serde_json::from_str::<Camera>(both).unwrap_err().to_string().contains("not both"));
+        let above = r#"{ "location": { "x": 0.0, "y": 0.0, "z": 0.0 }, "look_at": { "x": 0.0, "y": 2.0, "z": 0.0 } }"#;
+        assert!(serde_json::from_str::<Camera>(above).unwrap_err().to_string().contains("straight above or below"));
+    }
+}
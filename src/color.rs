@@ -1,11 +1,13 @@
 use std::ops;
 use raylib::ffi::Color;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::ray::Ray;
+use crate::real::to_f32;
 
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct RGBAColor {
     pub r: f32,
     pub g: f32,
@@ -13,13 +15,13 @@ pub struct RGBAColor {
     pub a: f32
 }
 
-impl Into<Color> for RGBAColor {
-    fn into(self) -> Color {
+impl From<RGBAColor> for Color {
+    fn from(color: RGBAColor) -> Self {
         Color {
-            r: self.r as u8,
-            g: self.g as u8,
-            b: self.b as u8,
-            a: self.a as u8
+            r: color.r as u8,
+            g: color.g as u8,
+            b: color.b as u8,
+            a: color.a as u8
         }
     }
 }
@@ -27,7 +29,7 @@ impl Into<Color> for RGBAColor {
 impl RGBAColor {
     
     pub fn new(r: f32, g: f32, b: f32) -> Option<Self> {
-        if vec![r, g, b].into_iter().any(|x| x < 0.0 || x > 255.0) {
+        if vec![r, g, b].into_iter().any(|x| !(0.0..=255.0).contains(&x)) {
             None
         } else {
             Some(Self{r, g, b, a: 255.0})
@@ -36,12 +38,18 @@ impl RGBAColor {
 
     pub fn white_blue_blend_over_y(r: &Ray) -> Self {
         let unit = r.direction.normalise();
-        let a = 0.5 * (unit.y + 1.0) as f32;
+        let a = 0.5 * to_f32(unit.y + 1.0);
         let white = Self::new(1.0, 1.0, 1.0).unwrap();
         let blue = Self::new(0.3, 0.5, 1.0).unwrap();
         ((1.0 - a) * white + a * blue).unwrap() * 255.999
     }
 
+    /* Heat map color going from blue at 0 to red at 1, values outside are clamped. */
+    pub fn heat(a: f32) -> Self {
+        let a = a.clamp(0.0, 1.0);
+        Self { r: a * 255.0, g: 0.0, b: (1.0 - a) * 255.0, a: 255.0 }
+    }
+
     pub fn as_rgb_tuple(self) -> (u8, u8, u8) {
         (self.r as u8, self.g as u8, self.b as u8)
     }
@@ -85,7 +93,11 @@ mod tests {
 
     use super::*;
 
+    use crate::real::Real;
+
+    use crate::material::Surface;
     use crate::sphere::Sphere;
+    use crate::traits::Intersectable;
     use crate::vec::Vec3;
     use crate::point::Point3;
     use crate::ppm::render_ppm_image_ascii;
@@ -96,7 +108,7 @@ mod tests {
         let aspect_ratio = 16.0 / 9.0;
         let image_width = 400;
 
-        let image_height = (image_width as f64 / aspect_ratio) as i32;
+        let image_height = (image_width as Real / aspect_ratio) as i32;
         // image height must be > 1
 
         // Camera information
@@ -105,7 +117,7 @@ mod tests {
         // its height and width using our choosen aspect ratio.
         let focal_length = 1.0;     // focal length is the length from origin to the viewport
         let viewport_height = 2.0;
-        let viewport_width = viewport_height * (image_width as i32 / image_height) as f64;
+        let viewport_width = viewport_height * (image_width / image_height) as Real;
         let camera_center = Point3::new(0.0, 0.0, 0.0);
 
         // We need two vectors across horizontal and down the vertical viewport edges
@@ -113,8 +125,8 @@ mod tests {
         let viewport_v = Vec3::new(0.0, -viewport_height, 0.0);
 
         // We also need two vectors that define that span the distance between two pixels.
-        let pixel_delta_u = viewport_u / image_width as f64;
-        let pixel_delta_v = viewport_v / image_height as f64;
+        let pixel_delta_u = viewport_u / image_width as Real;
+        let pixel_delta_v = viewport_v / image_height as Real;
 
         // Get a vector to the upper left pixel by using the focal lenght and our viewport vectors
         // then use our delta pixels to get the exact location of the pixel itself.
@@ -123,7 +135,7 @@ mod tests {
 
         let pixels: Vec<(u8, u8, u8)> = iproduct!(0..image_height, 0..image_width)
             .map(|(j, i)| {
-                let pixel_center = starting_pixel + (i as f64 * pixel_delta_u) + (j as f64 * pixel_delta_v);
+                let pixel_center = starting_pixel + (i as Real * pixel_delta_u) + (j as Real * pixel_delta_v);
                 let ray_direction = pixel_center - camera_center;
                 let ray = Ray::new(camera_center, ray_direction);
                 
@@ -133,7 +145,7 @@ mod tests {
             .collect();
     
         let output_file_path = Path::new("white_to_blue_gradient.ppm");
-        render_ppm_image_ascii(output_file_path, image_height, image_width, 255, &pixels);
+        render_ppm_image_ascii(output_file_path, image_height, image_width, 255, &pixels).unwrap();
     }
 
     #[test]
@@ -142,7 +154,7 @@ mod tests {
         let aspect_ratio = 16.0 / 9.0;
         let image_width = 1024;
 
-        let image_height = (image_width as f64 / aspect_ratio) as i32;
+        let image_height = (image_width as Real / aspect_ratio) as i32;
         // image height must be > 1
 
         // Camera information
@@ -151,7 +163,7 @@ mod tests {
         // its height and width using our choosen aspect ratio.
         let focal_length = 1.0;     // focal length is the length from origin to the viewport
         let viewport_height = 2.0;
-        let viewport_width = viewport_height * (image_width as i32 / image_height) as f64;
+        let viewport_width = viewport_height * (image_width / image_height) as Real;
         let camera_center = Point3::new(0.0, 0.0, 0.0);
 
         // We need two vectors across horizontal and down the vertical viewport edges
@@ -159,8 +171,8 @@ mod tests {
         let viewport_v = Vec3::new(0.0, -viewport_height, 0.0);
 
         // We also need two vectors that define that span the distance between two pixels.
-        let pixel_delta_u = viewport_u / image_width as f64;
-        let pixel_delta_v = viewport_v / image_height as f64;
+        let pixel_delta_u = viewport_u / image_width as Real;
+        let pixel_delta_v = viewport_v / image_height as Real;
 
         // Get a vector to the upper left pixel by using the focal lenght and our viewport vectors
         // then use our delta pixels to get the exact location of the pixel itself.
@@ -169,14 +181,15 @@ mod tests {
 
         let rays: Vec<Ray> = iproduct!(0..image_height, 0..image_width)
             .map(|(i, j)|{
-                let pixel_center = starting_pixel + (j as f64 * pixel_delta_u) + (i as f64 * pixel_delta_v);
+                let pixel_center = starting_pixel + (j as Real * pixel_delta_u) + (i as Real * pixel_delta_v);
                 let ray_direction = pixel_center - camera_center;
                 Ray::new(camera_center, ray_direction)
             })
             .collect();
 
+        let green = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
         let objects = vec![
-            Sphere{ origin: Point3{ x: 0.0, y: 0.0, z: -5.0 }, radius: 1.0}
+            Sphere{ origin: Point3{ x: 0.0, y: 0.0, z: -5.0 }, radius: 1.0, color: Some(green), material: None, surface: Surface::Color(green) }
         ];
 
         let mut pixels: Vec<(u8, u8, u8)> = vec![];
@@ -187,11 +200,12 @@ mod tests {
                     Some(hits) => {
                         let first_hit = hits.first().unwrap();
                         if first_hit.t > 0.0 {
+                            let n = first_hit.normal.cast::<f32>();
                             RGBAColor::new
                             (
-                                first_hit.normal.x as f32 + 1.0,
-                                first_hit.normal.y as f32+ 1.0,
-                                first_hit.normal.z as f32 + 1.0
+                                n.x + 1.0,
+                                n.y + 1.0,
+                                n.z + 1.0
                             ).unwrap() *
                             0.5 *
                             255.99
@@ -206,6 +220,6 @@ mod tests {
         }
 
         let output_file_path = Path::new("circle.ppm");
-        render_ppm_image_ascii(output_file_path, image_height, image_width, 255, &pixels);
+        render_ppm_image_ascii(output_file_path, image_height, image_width, 255, &pixels).unwrap();
     }
 }
@@ -0,0 +1,832 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::camera::Camera;
+use crate::color::RGBAColor;
+use crate::format::SceneFormat;
+use crate::gltf_scene;
+use crate::include::{self, Include, IncludeError};
+use crate::instance::{Instance, Placement, Shape};
+use crate::light::Light;
+use crate::material::{read_image, Material, Surface, Texture};
+use crate::mesh::{read_mesh_file, Mesh};
+use crate::node::{self, Node};
+use crate::plane::Plane;
+use crate::point::Point3;
+use crate::real::Real;
+use crate::schema::{self, SchemaError, Segment};
+use crate::sphere::Sphere;
+use crate::traits::Intersectable;
+
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub version: String,
+    #[serde(default)]
+    pub spheres: Vec<Sphere>,
+    #[serde(default)]
+    pub planes: Vec<Plane>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meshes: Vec<Mesh>,
+    // Shapes are only shown where an instance places them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub shapes: BTreeMap<String, Shape>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<Placement>,
+    // The scene graph. It is flattened into instances, lights and the
+    // camera while loading, so a loaded scene has no nodes left.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<Node>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<Light>,
+    // Where the viewer starts, the origin looking down the negative z
+    // axis when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<Camera>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub materials: BTreeMap<String, Material>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub textures: BTreeMap<String, Texture>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<Include>,
+    // The files the scene was read from. The first one is the file that
+    // was opened, the others were included. Includes are merged while
+    // loading, so the include list of a loaded scene is always empty.
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+    // Set when the scene had nodes, which are flattened into instances
+    // while loading. Saving it loses the tree.
+    #[serde(skip)]
+    pub flattened: bool,
+    // Parts of the scene that could not be shown, like the nodes of an
+    // imported glTF file that are scaled to nothing.
+    #[serde(skip)]
+    pub warnings: Vec<String>
+}
+
+pub fn read_config_from_file<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
+    load_config(path.as_ref(), &mut Vec::new())
+}
+
+/* Read a scene file together with the files it includes. The stack holds the files that are being read, an include of one of those would never end. */
+fn load_config(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Config, Box<dyn Error>> {
+    let canonical = fs::canonicalize(path)?;
+    if let Some(start) = stack.iter().position(|p| *p == canonical) {
+        let cycle = stack[start..].iter().cloned().chain([canonical]).collect();
+        return Err(Box::new(IncludeError::Cycle(cycle)));
+    }
+    // glTF files are models made in other tools, they have no includes
+    // of their own.
+    if gltf_scene::is_gltf(path) {
+        let mut config = gltf_scene::import_gltf(path)?;
+        config.files.push(path.to_path_buf());
+        config.validate()?;
+        return Ok(config);
+    }
+
+    let format = SceneFormat::from_path(path)?;
+    let source = fs::read_to_string(path)?;
+    let mut config = parse_config(&source, format)?;
+    config.files.push(path.to_path_buf());
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    read_mesh_files(&mut config, directory).map_err(|e| locate(e, &source, format))?;
+    read_images(&mut config, directory).map_err(|e| locate(e, &source, format))?;
+
+    let declared = Declared::of(&config);
+    stack.push(canonical);
+    for (i, include) in mem::take(&mut config.include).iter().enumerate() {
+        include::validate(include, i).map_err(|e| locate(e, &source, format))?;
+        let included_path = directory.join(&include.path);
+        let included = load_config(&included_path, stack)
+            .map_err(|e| IncludeError::InFile(included_path, e))?;
+        include::merge(&mut config, include::apply(included, include), i).map_err(|e| locate(e, &source, format))?;
+    }
+    stack.pop();
+
+    // Objects may use materials from included files, so the references
+    // are only checked once everything is merged. The lines of this file
+    // say nothing about objects that came from an included one.
+    config.validate().map_err(|e| if declared.contains(&e.path) { locate(e, &source, format) } else { e })?;
+    node::flatten(&mut config);
+    Ok(config)
+}
+
+struct Declared {
+    // The objects a scene file declares itself, before its includes are
+    // merged in: the length of the lists and the names in the maps.
+    lists: [(&'static str, usize); 5],
+    names: [(&'static str, BTreeSet<String>); 3]
+}
+
+impl Declared {
+    fn of(config: &Config) -> Self {
+        let names = |map: Vec<&String>| map.into_iter().cloned().collect();
+        Self {
+            lists: [
+                ("spheres", config.spheres.len()), ("planes", config.planes.len()), ("meshes", config.meshes.len()),
+                ("instances", config.instances.len()), ("lights", config.lights.len())
+            ],
+            names: [
+                ("materials", names(config.materials.keys().collect())),
+                ("textures", names(config.textures.keys().collect())),
+                ("shapes", names(config.shapes.keys().collect()))
+            ]
+        }
+    }
+
+    /* True unless the path leads into an object that was merged in from an included file. */
+    fn contains(&self, path: &[Segment]) -> bool {
+        match path {
+            [Segment::Key(list), Segment::Index(i), ..] => self.lists.iter()
+                .find(|(l, _)| l == list)
+                .is_none_or(|(_, len)| i < len),
+            [Segment::Key(map), Segment::Key(name), ..] => self.names.iter()
+                .find(|(m, _)| m == map)
+                .is_none_or(|(_, names)| names.contains(name)),
+            _ => true
+        }
+    }
+}
+
+/* Fill in the meshes that read their triangles from a file. The file joins the files of the scene so the viewer
+reloads when it changes. The path is dropped afterwards, a saved scene lists the triangles itself. */
+fn read_mesh_files(config: &mut Config, directory: &Path) -> Result<(), SchemaError> {
+    let listed = config.meshes.iter_mut().enumerate().map(|(i, mesh)| (vec![key("meshes"), Segment::Index(i)], mesh));
+    let shapes = config.shapes.iter_mut().filter_map(|(name, shape)| match shape {
+        Shape::Mesh(mesh) => Some((vec![key("shapes"), key(name)], mesh)),
+        Shape::Sphere(_) => None
+    });
+    for (path, mesh) in listed.chain(shapes) {
+        let Some(file) = mesh.path.take() else {
+            continue;
+        };
+        let path = [path, vec![key("path")]].concat();
+        if !mesh.vertices.is_empty() || !mesh.triangles.is_empty() {
+            return Err(SchemaError::new(path, "a mesh either lists its triangles or reads them from a file, not both".to_string()));
+        }
+        let file = directory.join(file);
+        let loaded = read_mesh_file(&file).map_err(|e| SchemaError::new(path, e.to_string()))?;
+        config.files.push(file);
+
+        mesh.vertices = loaded.vertices;
+        mesh.triangles = loaded.triangles;
+        if mesh.normals.is_empty() {
+            mesh.normals = loaded.normals;
+        }
+        if mesh.colors.is_empty() {
+            mesh.colors = loaded.colors;
+        }
+    }
+    Ok(())
+}
+
+/* Read the images of the image textures. Unlike a mesh file the path is kept, a saved scene refers to the
+image again. The files join the files of the scene so the viewer reloads when they change. */
+fn read_images(config: &mut Config, directory: &Path) -> Result<(), SchemaError> {
+    for (name, texture) in &mut config.textures {
+        let Texture::Image { path, index, image } = texture else {
+            continue;
+        };
+        let file = directory.join(&*path);
+        let read = read_image(&file, *index).map_err(|e| {
+            SchemaError::new(vec![key("textures"), key(name), key("path")], format!("cannot read image {}: {}", file.display(), e))
+        })?;
+        *image = Arc::new(read);
+        config.files.push(file);
+    }
+    Ok(())
+}
+
+/* Parse a scene file and upgrade it to the current version, without following its includes. */
+fn read_scene_value(source: &str, format: SceneFormat) -> Result<Value, SchemaError> {
+    let mut scene = format.parse(source).map_err(|e| SchemaError::new(Vec::new(), e.to_string()))?;
+    schema::migrate(&mut scene).map_err(|e| locate(e, source, format))?;
+    Ok(scene)
+}
+
+/* Parse a single scene file and upgrade it to the current version. Lines are only
+reported for JSON files, the other formats give the path to the problem. */
+pub fn parse_config(source: &str, format: SceneFormat) -> Result<Config, SchemaError> {
+    config_from_value(read_scene_value(source, format)?, source, format)
+}
+
+/* Add the line of the problem to an error, for JSON sources only. The line is looked up by walking the
+JSON text, it means nothing in another format. */
+fn locate(error: SchemaError, source: &str, format: SceneFormat) -> SchemaError {
+    match format {
+        SceneFormat::Json => error.locate(source),
+        _ => error
+    }
+}
+
+fn config_from_value(scene: Value, source: &str, format: SceneFormat) -> Result<Config, SchemaError> {
+
+    let config: Config = serde_path_to_error::deserialize(scene).map_err(|e| {
+        let path = e.path().iter().filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Seq { index } => Some(Segment::Index(*index)),
+            serde_path_to_error::Segment::Map { key } => Some(Segment::Key(key.clone())),
+            _ => None
+        }).collect();
+        locate(SchemaError::new(path, e.into_inner().to_string()), source, format)
+    })?;
+    Ok(config)
+}
+
+/* Translate a scene file to the format of the output file, upgrading it to the current version on the way. The
+scene is loaded like it is for viewing, so it is checked and its includes are merged into the output like a scene
+saved from the viewer. A glTF model is imported into a scene. */
+pub fn convert_scene_file(input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    read_config_from_file(input)?.write_to_file(output)
+}
+
+fn validate_color(color: &RGBAColor, path: Vec<Segment>) -> Result<(), SchemaError> {
+    for (channel, value) in [("r", color.r), ("g", color.g), ("b", color.b), ("a", color.a)] {
+        if !(0.0..=255.0).contains(&value) {
+            let mut path = path.clone();
+            path.push(Segment::Key(channel.to_string()));
+            return Err(SchemaError::new(path, format!("color channels must be between 0 and 255, got {}", value)));
+        }
+    }
+    Ok(())
+}
+
+fn validate_light(light: &Light, path: Vec<Segment>) -> Result<(), SchemaError> {
+    let color = match light {
+        Light::Point { color, .. } | Light::Directional { color, .. } | Light::Spot { color, .. } => color
+    };
+    validate_color(color, path)
+}
+
+/* Sizes like radii and scales have to be a number above zero, which NaN and infinity are not. */
+pub fn is_positive(value: Real) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+fn key(name: &str) -> Segment {
+    Segment::Key(name.to_string())
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectRef {
+    // Refers to an object in the config by its position in the
+    // list of spheres or planes.
+    Sphere(usize),
+    Plane(usize)
+}
+
+impl Config {
+
+    /* Save the scene in the format of the file extension. The output is canonical: fields
+    always come in the same order, materials and textures are sorted by name and empty
+    optional parts are left out, so saving the same scene twice gives the same file. */
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let format = SceneFormat::from_path(path.as_ref())?;
+        fs::write(path, format.write(self)?)?;
+        Ok(())
+    }
+
+    /* Check the things the types cannot express, like a sphere having a positive radius. */
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        let at = |list: &str, index: usize, field: &str| vec![key(list), Segment::Index(index), key(field)];
+
+        for (name, texture) in &self.textures {
+            let path = vec![key("textures"), key(name)];
+            match texture {
+                Texture::Solid { color } => validate_color(color, [path, vec![key("color")]].concat())?,
+                Texture::Checker { even, odd, scale } => {
+                    if !is_positive(*scale) {
+                        return Err(SchemaError::new([path, vec![key("scale")]].concat(), format!("scale must be positive, got {}", scale)));
+                    }
+                    validate_color(even, [path.clone(), vec![key("even")]].concat())?;
+                    validate_color(odd, [path, vec![key("odd")]].concat())?;
+                },
+                // Images are checked when they are read
+                Texture::Image { .. } => ()
+            }
+        }
+        for (name, material) in &self.materials {
+            let path = vec![key("materials"), key(name)];
+            for (field, value) in [("metallic", material.metallic), ("roughness", material.roughness)] {
+                if value.is_some_and(|v| !(0.0..=1.0).contains(&v)) {
+                    return Err(SchemaError::new([path, vec![key(field)]].concat(), format!("{} must be between 0 and 1", field)));
+                }
+            }
+            match (&material.color, &material.texture) {
+                (Some(color), None) => validate_color(color, [path, vec![key("color")]].concat())?,
+                (None, Some(texture)) if !self.textures.contains_key(texture) => {
+                    return Err(SchemaError::new([path, vec![key("texture")]].concat(), format!("unknown texture \"{}\"", texture)));
+                },
+                (None, Some(_)) => (),
+                _ => return Err(SchemaError::new(path, "a material needs either a color or a texture".to_string()))
+            }
+        }
+
+        for (i, sphere) in self.spheres.iter().enumerate() {
+            if !is_positive(sphere.radius) {
+                return Err(SchemaError::new(at("spheres", i, "radius"), format!("radius must be positive, got {}", sphere.radius)));
+            }
+            self.validate_surface(&sphere.color, &sphere.material, vec![key("spheres"), Segment::Index(i)])?;
+        }
+        for (i, plane) in self.planes.iter().enumerate() {
+            if plane.orient.magnitude() < 1e-9 {
+                return Err(SchemaError::new(at("planes", i, "orient"), "orient must not be a zero vector".to_string()));
+            }
+            self.validate_surface(&plane.color, &plane.material, vec![key("planes"), Segment::Index(i)])?;
+        }
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            self.validate_mesh(mesh, vec![key("meshes"), Segment::Index(i)])?;
+        }
+        for (name, shape) in &self.shapes {
+            let path = vec![key("shapes"), key(name)];
+            match shape {
+                Shape::Sphere(sphere) => {
+                    if !is_positive(sphere.radius) {
+                        return Err(SchemaError::new([path, vec![key("radius")]].concat(), format!("radius must be positive, got {}", sphere.radius)));
+                    }
+                    self.validate_surface(&sphere.color, &sphere.material, path)?;
+                },
+                Shape::Mesh(mesh) => self.validate_mesh(mesh, path)?
+            }
+        }
+        for (i, placement) in self.instances.iter().enumerate() {
+            if !self.shapes.contains_key(&placement.shape) {
+                return Err(SchemaError::new(at("instances", i, "shape"), format!("unknown shape \"{}\"", placement.shape)));
+            }
+            if let Some(scale) = placement.scale.filter(|s| !is_positive(*s)) {
+                return Err(SchemaError::new(at("instances", i, "scale"), format!("scale must be positive, got {}", scale)));
+            }
+            if let Some(rotation) = &placement.rotate {
+                rotation.check().map_err(|message| SchemaError::new(at("instances", i, "rotate"), message))?;
+            }
+        }
+        for (i, light) in self.lights.iter().enumerate() {
+            validate_light(light, at("lights", i, "color"))?;
+        }
+        let mut names = BTreeSet::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            self.validate_node(node, vec![key("nodes"), Segment::Index(i)], &mut names)?;
+        }
+        Ok(())
+    }
+
+    /* Check a node and its children. Node names are unique over the whole graph, the names seen so far are in names. */
+    fn validate_node(&self, node: &Node, path: Vec<Segment>, names: &mut BTreeSet<String>) -> Result<(), SchemaError> {
+        let field = |name: &str| [path.clone(), vec![key(name)]].concat();
+        if !names.insert(node.name.clone()) {
+            return Err(SchemaError::new(field("name"), format!("node \"{}\" is declared twice", node.name)));
+        }
+        if let Some(scale) = node.scale.filter(|s| !is_positive(*s)) {
+            return Err(SchemaError::new(field("scale"), format!("scale must be positive, got {}", scale)));
+        }
+        if let Some(rotation) = &node.rotate {
+            rotation.check().map_err(|message| SchemaError::new(field("rotate"), message))?;
+        }
+        for (i, shape) in node.shapes.iter().enumerate() {
+            if !self.shapes.contains_key(shape) {
+                return Err(SchemaError::new([field("shapes"), vec![Segment::Index(i)]].concat(), format!("unknown shape \"{}\"", shape)));
+            }
+        }
+        for (i, light) in node.lights.iter().enumerate() {
+            validate_light(light, [field("lights"), vec![Segment::Index(i), key("color")]].concat())?;
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            self.validate_node(child, [field("children"), vec![Segment::Index(i)]].concat(), names)?;
+        }
+        Ok(())
+    }
+
+    fn validate_mesh(&self, mesh: &Mesh, path: Vec<Segment>) -> Result<(), SchemaError> {
+        let field = |name: &str| [path.clone(), vec![key(name)]].concat();
+        for (t, triangle) in mesh.triangles.iter().enumerate() {
+            if let Some(corner) = triangle.iter().find(|&&corner| corner >= mesh.vertices.len()) {
+                let path = [field("triangles"), vec![Segment::Index(t)]].concat();
+                return Err(SchemaError::new(path, format!("vertex {} does not exist, the mesh has {} vertices", corner, mesh.vertices.len())));
+            }
+        }
+        for (name, length) in [("normals", mesh.normals.len()), ("colors", mesh.colors.len()), ("uvs", mesh.uvs.len())] {
+            if length != 0 && length != mesh.vertices.len() {
+                return Err(SchemaError::new(field(name), format!("expected one per vertex, got {} for {} vertices", length, mesh.vertices.len())));
+            }
+        }
+        for (c, color) in mesh.colors.iter().enumerate() {
+            validate_color(color, [field("colors"), vec![Segment::Index(c)]].concat())?;
+        }
+        // Vertex colors are enough to show the mesh, otherwise it needs a color or material like the other objects.
+        if mesh.colors.is_empty() || mesh.color.is_some() || mesh.material.is_some() {
+            self.validate_surface(&mesh.color, &mesh.material, path)?;
+        }
+        Ok(())
+    }
+
+    fn validate_surface(&self, color: &Option<RGBAColor>, material: &Option<String>, path: Vec<Segment>) -> Result<(), SchemaError> {
+        match (color, material) {
+            (Some(color), None) => validate_color(color, [path, vec![key("color")]].concat()),
+            (None, Some(material)) if !self.materials.contains_key(material) => {
+                Err(SchemaError::new([path, vec![key("material")]].concat(), format!("unknown material \"{}\"", material)))
+            },
+            (None, Some(_)) => Ok(()),
+            _ => Err(SchemaError::new(path, "an object needs either a color or a material".to_string()))
+        }
+    }
+
+    /* Resolve the color or material of an object. The config is validated, so the names exist. */
+    fn surface(&self, color: &Option<RGBAColor>, material: &Option<String>) -> Surface {
+        let material = material.as_ref().and_then(|name| self.materials.get(name));
+        match (color, material) {
+            (Some(color), _) => Surface::Color(*color),
+            (None, Some(Material { color: Some(color), .. })) => Surface::Color(*color),
+            (None, Some(Material { texture: Some(texture), .. })) => {
+                self.textures.get(texture).map(|t| Surface::Texture(t.clone())).unwrap_or_default()
+            },
+            _ => Surface::default()
+        }
+    }
+
+    /* Build the objects the renderer shoots rays at. The spheres come first, followed by the planes, meshes and instances. */
+    pub fn intersectables(&self) -> Vec<Box<dyn Intersectable>> {
+        let mut intersectables: Vec<Box<dyn Intersectable>> = Vec::new();
+        for sphere in &self.spheres {
+            let surface = self.surface(&sphere.color, &sphere.material);
+            intersectables.push(Box::new(Sphere { surface, ..sphere.clone() }));
+        }
+        for plane in &self.planes {
+            let surface = self.surface(&plane.color, &plane.material);
+            intersectables.push(Box::new(Plane { surface, ..plane.clone() }));
+        }
+        for mesh in &self.meshes {
+            let surface = self.surface(&mesh.color, &mesh.material);
+            intersectables.push(Box::new(Mesh { surface, bounds: mesh.bounds(), ..mesh.clone() }));
+        }
+
+        // Every shape is built once and shared by the instances placing it.
+        let shapes: BTreeMap<&String, Arc<dyn Intersectable>> = self.shapes.iter().map(|(name, shape)| {
+            let object: Arc<dyn Intersectable> = match shape {
+                Shape::Sphere(sphere) => {
+                    Arc::new(Sphere { surface: self.surface(&sphere.color, &sphere.material), ..sphere.clone() })
+                },
+                Shape::Mesh(mesh) => {
+                    Arc::new(Mesh { surface: self.surface(&mesh.color, &mesh.material), bounds: mesh.bounds(), ..mesh.clone() })
+                }
+            };
+            (name, object)
+        }).collect();
+        for placement in &self.instances {
+            intersectables.push(Box::new(Instance::new(shapes[&placement.shape].clone(), placement.transform())));
+        }
+        intersectables
+    }
+
+    /* Find the object in the config belonging to an index in the list of intersectables. */
+    pub fn object_at(&self, index: usize) -> Option<ObjectRef> {
+        if index < self.spheres.len() {
+            Some(ObjectRef::Sphere(index))
+        } else if index < self.spheres.len() + self.planes.len() {
+            Some(ObjectRef::Plane(index - self.spheres.len()))
+        } else {
+            None
+        }
+    }
+
+    /* The inverse of object_at */
+    pub fn index_of(&self, object: ObjectRef) -> usize {
+        match object {
+            ObjectRef::Sphere(i) => i,
+            ObjectRef::Plane(i) => self.spheres.len() + i
+        }
+    }
+
+    /* The point the camera turns to when it focuses on an object, the center of a sphere or the origin of a plane. */
+    pub fn origin_of(&self, object: ObjectRef) -> Point3 {
+        match object {
+            ObjectRef::Sphere(i) => self.spheres[i].origin,
+            ObjectRef::Plane(i) => self.planes[i].origin
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use super::*;
+
+    use crate::hit::Hit;
+    use crate::normal::Normal3;
+    use crate::point::Point3;
+    use crate::real::TOLERANCE;
+    use crate::vec::Vec3;
+
+    fn parse(source: &str) -> Result<Config, SchemaError> {
+        let config = parse_config(source, SceneFormat::Json)?;
+        config.validate().map_err(|e| e.locate(source))?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_parse_config_reports_path_and_line() {
+        let source = r#"{
+    "version": "1.0",
+    "spheres": [
+        {
+            "origin": { "x": 0.0, "y": 0.0, "z": -5.0 },
+            "radius": -1.5,
+            "color": { "r": 0.0, "g": 255.0, "b": 0.0, "a": 255.0 }
+        }
+    ]
+}"#;
+        let error = parse(source).unwrap_err();
+        assert_eq!(error.to_string(), "spheres[0].radius (line 6): radius must be positive, got -1.5");
+
+        let unknown = source.replace("\"radius\"", "\"radios\"");
+        let error = parse(&unknown).unwrap_err();
+        assert_eq!(schema::format_path(&error.path), "spheres[0].radios");
+        assert_eq!(error.line, Some(6));
+        assert!(error.message.contains("unknown field `radios`"));
+    }
+
+    #[test]
+    fn test_planes_need_a_normal() {
+        let source = r#"{
+    "version": "1.0",
+    "spheres": [],
+    "planes": [
+        {
+            "origin": { "x": 0.0, "y": -1.0, "z": 0.0 },
+            "orient": { "x": 0.0, "y": 0.0, "z": 0.0 },
+            "color": { "r": 0.0, "g": 0.0, "b": 255.0, "a": 255.0 }
+        }
+    ]
+}"#;
+        let error = parse(source).unwrap_err();
+        assert_eq!(error.to_string(), "planes[0].orient (line 7): orient must not be a zero vector");
+
+        let config = parse(&source.replace("\"y\": 0.0, \"z\": 0.0 }", "\"y\": 1.0, \"z\": 0.0 }")).unwrap();
+        assert_eq!(config.version, schema::CURRENT_VERSION);
+        assert_eq!(config.planes[0].orient.y, 1.0);
+    }
+
+    #[test]
+    fn test_materials_are_resolved_by_name() {
+        let source = r#"{
+    "version": "1.0",
+    "textures": {
+        "floor": { "type": "checker", "even": { "r": 255.0, "g": 255.0, "b": 255.0, "a": 255.0 }, "odd": { "r": 0.0, "g": 0.0, "b": 0.0, "a": 255.0 }, "scale": 1.0 }
+    },
+    "materials": {
+        "tiles": { "texture": "floor" }
+    },
+    "planes": [
+        {
+            "origin": { "x": 0.0, "y": 0.0, "z": 0.0 },
+            "orient": { "x": 0.0, "y": 1.0, "z": 0.0 },
+            "material": "tiles"
+        }
+    ]
+}"#;
+        let config = parse(source).unwrap();
+        let plane = &config.intersectables()[0];
+        assert_eq!(plane.color_at(&Hit::new(1.0, Point3::new(0.5, 0.0, 0.5), Normal3::new(0.0, 1.0, 0.0))).r, 255.0);
+        assert_eq!(plane.color_at(&Hit::new(1.0, Point3::new(1.5, 0.0, 0.5), Normal3::new(0.0, 1.0, 0.0))).r, 0.0);
+
+        let error = parse(&source.replace("\"material\": \"tiles\"", "\"material\": \"tile\"")).unwrap_err();
+        assert_eq!(error.to_string(), "planes[0].material (line 13): unknown material \"tile\"");
+
+        let error = parse(&source.replace("\"texture\": \"floor\"", "\"texture\": \"wall\"")).unwrap_err();
+        assert_eq!(error.to_string(), "materials.tiles.texture (line 7): unknown texture \"wall\"");
+
+        let mut config = config;
+        config.textures.insert("floor".to_string(), Texture::Checker { even: RGBAColor::new(0.0, 0.0, 0.0).unwrap(), odd: RGBAColor::new(0.0, 0.0, 0.0).unwrap(), scale: Real::INFINITY });
+        assert_eq!(config.validate().unwrap_err().to_string(), "textures.floor.scale: scale must be positive, got inf");
+    }
+
+    /* A fresh directory for a test, the process id keeps test runs that happen at the same time apart. */
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("raytracer_{}_test_{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_includes_are_merged_and_cycles_detected() {
+        let directory = test_directory("include");
+        fs::create_dir_all(directory.join("props")).unwrap();
+        write_file(&directory.join("scene.json"), r#"{
+    "version": "1.0",
+    "include": [ { "path": "props/ball.json", "prefix": "ball.", "translate": { "x": 0.0, "y": 0.0, "z": -5.0 }, "scale": 2.0 } ],
+    "spheres": [ { "origin": { "x": 3.0, "y": 0.0, "z": -5.0 }, "radius": 1.0, "material": "ball.red" } ]
+}"#);
+        write_file(&directory.join("props/ball.json"), r#"{
+    "version": "1.0",
+    "materials": { "red": { "color": { "r": 255.0, "g": 0.0, "b": 0.0, "a": 255.0 } } },
+    "spheres": [ { "origin": { "x": 0.0, "y": 1.0, "z": 0.0 }, "radius": 0.5, "material": "red" } ]
+}"#);
+
+        let config = read_config_from_file(directory.join("scene.json")).unwrap();
+        assert_eq!(config.spheres.len(), 2);
+        assert_eq!(config.spheres[1].origin.y, 2.0);
+        assert_eq!(config.spheres[1].origin.z, -5.0);
+        assert_eq!(config.spheres[1].radius, 1.0);
+        assert_eq!(config.spheres[1].material.as_deref(), Some("ball.red"));
+        assert_eq!(config.files.len(), 2);
+
+        // only the sphere and the material of the scene itself are found in its source
+        let declared = Declared::of(&parse_config(&fs::read_to_string(directory.join("scene.json")).unwrap(), SceneFormat::Json).unwrap());
+        assert!(declared.contains(&[key("spheres"), Segment::Index(0), key("radius")]));
+        assert!(!declared.contains(&[key("spheres"), Segment::Index(1), key("radius")]));
+        assert!(!declared.contains(&[key("materials"), key("ball.red")]));
+
+        // make the ball include the scene that includes it
+        write_file(&directory.join("props/ball.json"), r#"{ "version": "1.0", "include": [ { "path": "../scene.json" } ] }"#);
+        let error = read_config_from_file(directory.join("scene.json")).unwrap_err();
+        assert!(error.to_string().contains("scene files include each other"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_includes_can_be_rotated() {
+        let directory = test_directory("rotate");
+        write_file(&directory.join("scene.json"), r#"{
+    "version": "1.0",
+    "include": [ { "path": "wall.json", "rotate": { "yaw": 1.5707963267948966 }, "translate": { "x": 0.0, "y": 1.0, "z": 0.0 } } ]
+}"#);
+        write_file(&directory.join("wall.json"), r#"{
+    "version": "1.0",
+    "spheres": [ { "origin": { "x": 0.0, "y": 0.0, "z": -2.0 }, "radius": 1.0, "color": { "r": 255.0, "g": 0.0, "b": 0.0, "a": 255.0 } } ],
+    "planes": [ { "origin": { "x": 0.0, "y": 0.0, "z": -4.0 }, "orient": { "x": 0.0, "y": 0.0, "z": 1.0 }, "color": { "r": 255.0, "g": 0.0, "b": 0.0, "a": 255.0 } } ]
+}"#);
+
+        // turning to the right moves what was in front of the camera to its right
+        let config = read_config_from_file(directory.join("scene.json")).unwrap();
+        let origin = config.spheres[0].origin;
+        assert!((origin - Point3::new(2.0, 1.0, 0.0)).magnitude() < TOLERANCE);
+        assert!((config.planes[0].orient.to_vec() - Vec3::new(-1.0, 0.0, 0.0)).magnitude() < TOLERANCE);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_instances_place_shared_shapes() {
+        let directory = test_directory("instance");
+        write_file(&directory.join("scene.json"), r#"{
+    "version": "1.0",
+    "include": [ { "path": "forest.json", "prefix": "forest.", "translate": { "x": 0.0, "y": 0.0, "z": -10.0 }, "scale": 2.0 } ]
+}"#);
+        write_file(&directory.join("forest.json"), r#"{
+    "version": "1.0",
+    "shapes": { "tree": { "type": "sphere", "origin": { "x": 0.0, "y": 0.0, "z": 0.0 }, "radius": 1.0, "material": "leaves" } },
+    "materials": { "leaves": { "color": { "r": 0.0, "g": 255.0, "b": 0.0, "a": 255.0 } } },
+    "instances": [
+        { "shape": "tree", "translate": { "x": 3.0, "y": 0.0, "z": 0.0 } },
+        { "shape": "tree", "translate": { "x": -3.0, "y": 0.0, "z": 0.0 }, "scale": 0.5 }
+    ]
+}"#);
+
+        let config = read_config_from_file(directory.join("scene.json")).unwrap();
+        assert_eq!(config.instances[1].shape, "forest.tree");
+        let intersectables = config.intersectables();
+        let (min, max) = intersectables[1].bounding_box().unwrap();
+        // the second tree is halved, then doubled by the include and moved to x = -6
+        assert!((min.x + 7.0).abs() < TOLERANCE && (max.x + 5.0).abs() < TOLERANCE && (max.z + 9.0).abs() < TOLERANCE);
+
+        let unknown = r#"{ "version": "1.0", "instances": [ { "shape": "rock" } ] }"#;
+        assert_eq!(parse(unknown).unwrap_err().to_string(), "instances[0].shape (line 1): unknown shape \"rock\"");
+
+        // Scales that would leave the shape where it is are rejected, not ignored
+        let mut endless = config.clone();
+        endless.instances[0].scale = Some(Real::INFINITY);
+        assert_eq!(endless.validate().unwrap_err().to_string(), "instances[0].scale: scale must be positive, got inf");
+        let include = Include { path: PathBuf::from("forest.json"), prefix: String::new(), rotate: None, translate: None, scale: Some(Real::NAN) };
+        assert_eq!(include::validate(&include, 0).unwrap_err().to_string(), "include[0].scale: scale must be positive, got NaN");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_node_names_and_shapes_are_checked() {
+        let twice = r#"{ "version": "1.0", "nodes": [ { "name": "arm", "children": [ { "name": "arm" } ] } ] }"#;
+        assert_eq!(parse(twice).unwrap_err().to_string(), "nodes[0].children[0].name (line 1): node \"arm\" is declared twice");
+        let unknown = r#"{ "version": "1.0", "nodes": [ { "name": "arm", "shapes": [ "rock" ] } ] }"#;
+        assert_eq!(parse(unknown).unwrap_err().to_string(), "nodes[0].shapes[0] (line 1): unknown shape \"rock\"");
+
+        let mut config = parse(r#"{ "version": "1.0", "nodes": [ { "name": "arm", "scale": 2.0 } ] }"#).unwrap();
+        config.nodes[0].scale = Some(Real::NAN);
+        assert_eq!(config.validate().unwrap_err().to_string(), "nodes[0].scale: scale must be positive, got NaN");
+    }
+
+    #[test]
+    fn test_meshes_are_read_from_files() {
+        let directory = test_directory("mesh_file");
+        write_file(&directory.join("part.stl"), "solid part
+facet normal 0 0 1 outer loop vertex 0 0 0 vertex 1 0 0 vertex 0 1 0 endloop endfacet
+endsolid part");
+        write_file(&directory.join("scene.json"), r#"{
+    "version": "1.0",
+    "meshes": [ { "path": "part.stl", "color": { "r": 200.0, "g": 200.0, "b": 200.0, "a": 255.0 } } ]
+}"#);
+
+        let config = read_config_from_file(directory.join("scene.json")).unwrap();
+        assert_eq!(config.meshes[0].triangles, vec![[0, 1, 2]]);
+        assert_eq!(config.meshes[0].vertices[1].x, 1.0);
+        assert!(config.files.contains(&directory.join("part.stl")));
+
+        write_file(&directory.join("scene.json"), r#"{
+    "version": "1.0",
+    "meshes": [ { "path": "missing.ply", "color": { "r": 200.0, "g": 200.0, "b": 200.0, "a": 255.0 } } ]
+}"#);
+        let error = read_config_from_file(directory.join("scene.json")).unwrap_err();
+        assert!(error.to_string().starts_with("meshes[0].path (line 3)"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_scenes_are_checked_and_merged_when_converted() {
+        let directory = test_directory("convert");
+        write_file(&directory.join("scene.json"), r#"{
+    "version": "1.0",
+    "spheres": [ { "origin": { "x": 0.0, "y": 0.0, "z": -5.0 }, "radius": 1.0, "material": "missing" } ]
+}"#);
+
+        let error = convert_scene_file(&directory.join("scene.json"), &directory.join("scene.toml")).unwrap_err();
+        assert!(error.to_string().starts_with("spheres[0].material (line 3)"), "{}", error);
+        assert!(!directory.join("scene.toml").exists());
+
+        // A valid scene is written with its includes merged in
+        write_file(&directory.join("ball.json"), r#"{
+    "version": "1.0",
+    "materials": { "red": { "color": { "r": 255.0, "g": 0.0, "b": 0.0, "a": 255.0 } } }
+}"#);
+        write_file(&directory.join("scene.json"), r#"{
+    "version": "1.0",
+    "include": [ { "path": "ball.json", "prefix": "ball." } ],
+    "spheres": [ { "origin": { "x": 0.0, "y": 0.0, "z": -5.0 }, "radius": 1.0, "material": "ball.red" } ]
+}"#);
+        convert_scene_file(&directory.join("scene.json"), &directory.join("scene.toml")).unwrap();
+        let converted = parse_config(&fs::read_to_string(directory.join("scene.toml")).unwrap(), SceneFormat::Toml).unwrap();
+        assert!(converted.include.is_empty() && converted.materials.contains_key("ball.red"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_scene_round_trips_through_every_format() {
+        let source = r#"{
+    "version": "1.0",
+    "textures": { "tiles": { "type": "checker", "even": { "r": 255.0, "g": 255.0, "b": 255.0, "a": 255.0 }, "odd": { "r": 0.0, "g": 0.0, "b": 0.0, "a": 255.0 }, "scale": 0.5 } },
+    "materials": { "floor": { "texture": "tiles" } },
+    "spheres": [ { "origin": { "x": 0.0, "y": 0.0, "z": -5.0 }, "radius": 1.5, "color": { "r": 0.0, "g": 255.0, "b": 0.0, "a": 255.0 } } ],
+    "planes": [ { "origin": { "x": 0.0, "y": -1.5, "z": 0.0 }, "orient": { "x": 0.0, "y": 1.0, "z": 0.0 }, "material": "floor" } ]
+}"#;
+        let config = parse(source).unwrap();
+        let canonical = SceneFormat::Json.write(&config).unwrap();
+        assert!(canonical.starts_with("{\n    \"version\": \"1.0\",\n    \"spheres\""));
+        assert!(!canonical.contains("\"include\""));
+
+        for format in [SceneFormat::Json, SceneFormat::Toml, SceneFormat::Yaml, SceneFormat::Ron] {
+            let written = format.write(&config).unwrap();
+            let read = parse_config(&written, format).unwrap();
+            assert_eq!(SceneFormat::Json.write(&read).unwrap(), canonical, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_radius_must_be_a_number() {
+        // Scenes go through JSON values while loading, which have no NaN
+        let source = "version = \"1.0\"\n\n[[spheres]]\nradius = nan\ncolor = { r = 0.0, g = 255.0, b = 0.0, a = 255.0 }\norigin = { x = 0.0, y = 0.0, z = -5.0 }\n";
+        for source in [source.to_string(), source.replace("nan", "inf")] {
+            let error = parse_config(&source, SceneFormat::Toml).unwrap_err();
+            assert_eq!(schema::format_path(&error.path), "spheres[0].radius");
+        }
+
+        let mut config = parse_config(&source.replace("nan", "1.0"), SceneFormat::Toml).unwrap();
+        for radius in [Real::NAN, Real::INFINITY] {
+            config.spheres[0].radius = radius;
+            assert_eq!(config.validate().unwrap_err().to_string(), format!("spheres[0].radius: radius must be positive, got {}", radius));
+        }
+    }
+
+    #[test]
+    fn test_lines_are_only_given_for_json() {
+        let source = "version = \"1.0\"\n\n[[spheres]]\nradius = 1.0\ncolour = 1\n";
+        let error = parse_config(source, SceneFormat::Toml).unwrap_err();
+        assert!(error.to_string().starts_with("spheres[0].colour: unknown field `colour`"), "{}", error);
+        assert!(!error.to_string().contains("line"));
+    }
+}
@@ -0,0 +1,332 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use raylib::prelude::*;
+use serde::Deserialize;
+
+use crate::camera::Camera;
+use crate::point::Point3;
+use crate::quaternion::Quaternion;
+use crate::real::Real;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Key(pub KeyboardKey);
+
+#[derive(Debug, Clone)]
+pub struct UnknownKeyError(String);
+
+impl fmt::Display for UnknownKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown key name '{}'.", self.0)
+    }
+}
+
+impl Error for UnknownKeyError {}
+
+impl TryFrom<String> for Key {
+    type Error = UnknownKeyError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        key_from_name(&name)
+            .map(Key)
+            .ok_or(UnknownKeyError(name))
+    }
+}
+
+/* Map a human readable key name like "W", "5", "SPACE" or "LEFT_SHIFT" to a raylib key. */
+pub fn key_from_name(name: &str) -> Option<KeyboardKey> {
+    use KeyboardKey::*;
+
+    let name = name.to_uppercase();
+    let letters = [
+        KEY_A, KEY_B, KEY_C, KEY_D, KEY_E, KEY_F, KEY_G, KEY_H, KEY_I, KEY_J, KEY_K, KEY_L, KEY_M,
+        KEY_N, KEY_O, KEY_P, KEY_Q, KEY_R, KEY_S, KEY_T, KEY_U, KEY_V, KEY_W, KEY_X, KEY_Y, KEY_Z
+    ];
+    let digits = [KEY_ZERO, KEY_ONE, KEY_TWO, KEY_THREE, KEY_FOUR, KEY_FIVE, KEY_SIX, KEY_SEVEN, KEY_EIGHT, KEY_NINE];
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return match c {
+            'A'..='Z' => Some(letters[c as usize - 'A' as usize]),
+            '0'..='9' => Some(digits[c as usize - '0' as usize]),
+            _ => None
+        };
+    }
+
+    let key = match name.as_str() {
+        "SPACE" => KEY_SPACE,
+        "ENTER" => KEY_ENTER,
+        "TAB" => KEY_TAB,
+        "ESCAPE" => KEY_ESCAPE,
+        "BACKSPACE" => KEY_BACKSPACE,
+        "UP" => KEY_UP,
+        "DOWN" => KEY_DOWN,
+        "LEFT" => KEY_LEFT,
+        "RIGHT" => KEY_RIGHT,
+        "PAGE_UP" => KEY_PAGE_UP,
+        "PAGE_DOWN" => KEY_PAGE_DOWN,
+        "LEFT_SHIFT" => KEY_LEFT_SHIFT,
+        "RIGHT_SHIFT" => KEY_RIGHT_SHIFT,
+        "LEFT_CONTROL" => KEY_LEFT_CONTROL,
+        "RIGHT_CONTROL" => KEY_RIGHT_CONTROL,
+        "LEFT_ALT" => KEY_LEFT_ALT,
+        "RIGHT_ALT" => KEY_RIGHT_ALT,
+        _ => return None
+    };
+    Some(key)
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    // Every action can be bound to multiple keys. Bindings missing
+    // from the configuration file keep their default keys.
+    pub forward: Vec<Key>,
+    pub backward: Vec<Key>,
+    pub left: Vec<Key>,
+    pub right: Vec<Key>,
+    pub up: Vec<Key>,
+    pub down: Vec<Key>,
+    pub toggle_orbit: Vec<Key>,
+    pub focus: Vec<Key>
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use KeyboardKey::*;
+
+        Self {
+            forward: vec![Key(KEY_W), Key(KEY_UP)],
+            backward: vec![Key(KEY_S), Key(KEY_DOWN)],
+            left: vec![Key(KEY_A), Key(KEY_LEFT)],
+            right: vec![Key(KEY_D), Key(KEY_RIGHT)],
+            up: vec![Key(KEY_SPACE)],
+            down: vec![Key(KEY_LEFT_SHIFT)],
+            toggle_orbit: vec![Key(KEY_O)],
+            focus: vec![Key(KEY_F)]
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+fn any_down(rl: &RaylibHandle, keys: &[Key]) -> bool {
+    keys.iter().any(|k| rl.is_key_down(k.0))
+}
+
+fn any_pressed(rl: &RaylibHandle, keys: &[Key]) -> bool {
+    keys.iter().any(|k| rl.is_key_pressed(k.0))
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    // Move freely through the scene and look around with the mouse.
+    FreeFly,
+    // Rotate around a target point, the mouse wheel changes the distance.
+    Orbit
+}
+
+// How long turning towards a focused object takes, in seconds
+const TURN_DURATION: Real = 0.4;
+
+struct Turn {
+    // The camera turning towards a point it focuses on. The orientation
+    // is interpolated from the one it had when the turn started.
+    from: Quaternion,
+    to: Quaternion,
+    target: Point3,
+    elapsed: Real
+}
+
+pub struct CameraController {
+    // Translates keyboard and mouse input into camera movement. Looking
+    // around is done by dragging with the right mouse button so the
+    // cursor stays available for everything else.
+    pub mode: CameraMode,
+    pub bindings: KeyBindings,
+    // movement speed in units per second
+    pub move_speed: Real,
+    // rotation in radians per pixel the mouse moved
+    pub mouse_sensitivity: Real,
+    pub orbit_target: Point3,
+    pub orbit_distance: Real,
+    turn: Option<Turn>
+}
+
+impl CameraController {
+    pub fn new(bindings: KeyBindings) -> Self {
+        Self {
+            mode: CameraMode::FreeFly,
+            bindings,
+            move_speed: 2.5,
+            mouse_sensitivity: 0.003,
+            orbit_target: Point3::new(0.0, 0.0, -5.0),
+            orbit_distance: 5.0,
+            turn: None
+        }
+    }
+
+    /* Apply this frame's input to the camera, returns true if the camera changed. The focus key turns the camera
+    towards the point of the selected object, if there is one. */
+    pub fn update(&mut self, rl: &RaylibHandle, camera: &mut Camera, selected: Option<Point3>) -> bool {
+        let mut changed = false;
+
+        if let (true, Some(target)) = (any_pressed(rl, &self.bindings.focus), selected) {
+            self.focus(camera, target);
+        }
+        changed |= self.advance_turn(camera, rl.get_frame_time() as Real);
+
+        if any_pressed(rl, &self.bindings.toggle_orbit) {
+            self.mode = match self.mode {
+                CameraMode::FreeFly => {
+                    self.orbit_target = camera.location + self.orbit_distance * camera.forward();
+                    CameraMode::Orbit
+                },
+                CameraMode::Orbit => CameraMode::FreeFly
+            };
+        }
+
+        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
+            let delta = rl.get_mouse_delta();
+            if delta.x != 0.0 || delta.y != 0.0 {
+                // Looking around takes over from a turn in progress
+                self.turn = None;
+                self.rotate(camera, delta.x as Real, delta.y as Real);
+                changed = true;
+            }
+        }
+
+        let wheel = rl.get_mouse_wheel_move() as Real;
+        if wheel != 0.0 {
+            match self.mode {
+                CameraMode::FreeFly => camera.vfov = (camera.vfov - 5.0 * wheel).clamp(10.0, 120.0),
+                CameraMode::Orbit => self.orbit_distance = (self.orbit_distance * (0.9 as Real).powf(wheel)).max(0.1)
+            }
+            changed = true;
+        }
+
+        // Keys held together with Ctrl are shortcuts like Ctrl+S, not movement.
+        let control = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+        let direction = if control { None } else { self.movement_direction(rl, camera).try_normalise() };
+        if let Some(direction) = direction {
+            let step = self.move_speed * rl.get_frame_time() as Real * direction;
+            match self.mode {
+                CameraMode::FreeFly => camera.location = camera.location + step,
+                CameraMode::Orbit => self.orbit_target = self.orbit_target + step
+            }
+            changed = true;
+        }
+
+        if changed && self.mode == CameraMode::Orbit {
+            camera.location = self.orbit_target - self.orbit_distance * camera.forward();
+        }
+        changed
+    }
+
+    /* Start turning the camera towards a point. The camera stays where it is and orbits the point once it looks at it.
+    A point straight above or below the camera cannot be looked at without rolling, the camera does not turn then. */
+    fn focus(&mut self, camera: &Camera, target: Point3) {
+        let mut aimed = *camera;
+        if aimed.look_at(target) {
+            self.mode = CameraMode::FreeFly;
+            self.turn = Some(Turn { from: camera.orientation(), to: aimed.orientation(), target, elapsed: 0.0 });
+        }
+    }
+
+    /* Move a turn in progress on by some seconds, returns true if the camera changed. */
+    fn advance_turn(&mut self, camera: &mut Camera, seconds: Real) -> bool {
+        let Some(turn) = &mut self.turn else {
+            return false;
+        };
+        turn.elapsed = (turn.elapsed + seconds).min(TURN_DURATION);
+        let orientation = turn.from.slerp(&turn.to, turn.elapsed / TURN_DURATION);
+        camera.look_along(orientation.rotate(&Vec3::new(0.0, 0.0, -1.0)));
+
+        if turn.elapsed >= TURN_DURATION {
+            self.mode = CameraMode::Orbit;
+            self.orbit_target = turn.target;
+            self.orbit_distance = camera.location.distance(&turn.target);
+            self.turn = None;
+        }
+        true
+    }
+
+    fn rotate(&self, camera: &mut Camera, dx: Real, dy: Real) {
+        // Keep the pitch just short of straight up or down, the camera
+        // basis flips over at exactly 90 degrees.
+        let max_pitch = (89.0 as Real).to_radians();
+        camera.yaw += dx * self.mouse_sensitivity;
+        camera.pitch = (camera.pitch - dy * self.mouse_sensitivity).clamp(-max_pitch, max_pitch);
+    }
+
+    fn movement_direction(&self, rl: &RaylibHandle, camera: &Camera) -> Vec3 {
+        let b = &self.bindings;
+        let world_up = Vec3::new(0.0, 1.0, 0.0);
+        let mut direction = Vec3::new(0.0, 0.0, 0.0);
+
+        if any_down(rl, &b.forward) { direction += camera.forward(); }
+        if any_down(rl, &b.backward) { direction -= camera.forward(); }
+        if any_down(rl, &b.right) { direction += camera.right(); }
+        if any_down(rl, &b.left) { direction -= camera.right(); }
+        if any_down(rl, &b.up) { direction += world_up; }
+        if any_down(rl, &b.down) { direction -= world_up; }
+        direction
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_key_from_name() {
+        assert_eq!(key_from_name("w"), Some(KeyboardKey::KEY_W));
+        assert_eq!(key_from_name("7"), Some(KeyboardKey::KEY_SEVEN));
+        assert_eq!(key_from_name("left_shift"), Some(KeyboardKey::KEY_LEFT_SHIFT));
+        assert_eq!(key_from_name("HYPER"), None);
+    }
+
+    #[test]
+    fn test_partial_bindings_keep_defaults() {
+        let bindings: KeyBindings = serde_json::from_str(r#"{ "forward": ["I"] }"#).unwrap();
+
+        assert_eq!(bindings.forward, vec![Key(KeyboardKey::KEY_I)]);
+        assert_eq!(bindings.backward, KeyBindings::default().backward);
+    }
+
+    #[test]
+    fn test_focus_turns_towards_the_target_and_orbits_it() {
+        let mut controller = CameraController::new(KeyBindings::default());
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0));
+        let target = Point3::new(4.0, 0.0, 0.0);
+        controller.focus(&camera, target);
+
+        // Half way through the turn the camera looks half way from the
+        // negative z axis to the positive x axis.
+        assert!(controller.advance_turn(&mut camera, TURN_DURATION / 2.0));
+        assert!((camera.yaw - crate::real::consts::FRAC_PI_4).abs() < 1e-4);
+        assert_eq!(controller.mode, CameraMode::FreeFly);
+
+        assert!(controller.advance_turn(&mut camera, TURN_DURATION));
+        assert!((camera.forward() - Vec3::new(1.0, 0.0, 0.0)).magnitude() < 1e-4);
+        assert_eq!(controller.mode, CameraMode::Orbit);
+        assert!((controller.orbit_distance - 4.0).abs() < 1e-4);
+        assert!(!controller.advance_turn(&mut camera, TURN_DURATION));
+    }
+}
@@ -0,0 +1,301 @@
+use raylib::prelude::*;
+
+use crate::config::{Config, ObjectRef};
+use crate::real::{to_f32, Real};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Property {
+    X,
+    Y,
+    Z,
+    Radius,
+    NormalX,
+    NormalY,
+    NormalZ,
+    Red,
+    Green,
+    Blue
+}
+
+const SPHERE_PROPERTIES: [Property; 7] = [
+    Property::X, Property::Y, Property::Z, Property::Radius,
+    Property::Red, Property::Green, Property::Blue
+];
+
+const PLANE_PROPERTIES: [Property; 9] = [
+    Property::X, Property::Y, Property::Z,
+    Property::NormalX, Property::NormalY, Property::NormalZ,
+    Property::Red, Property::Green, Property::Blue
+];
+
+impl Property {
+    fn name(&self) -> &'static str {
+        match self {
+            Property::X => "x",
+            Property::Y => "y",
+            Property::Z => "z",
+            Property::Radius => "radius",
+            Property::NormalX => "normal x",
+            Property::NormalY => "normal y",
+            Property::NormalZ => "normal z",
+            Property::Red => "red",
+            Property::Green => "green",
+            Property::Blue => "blue"
+        }
+    }
+
+    /* The amount a single key press changes the value */
+    fn step(&self) -> Real {
+        match self {
+            Property::Red | Property::Green | Property::Blue => 5.0,
+            _ => 0.1
+        }
+    }
+}
+
+/* The editable properties of an object. The color is only listed when it is given inline, a shared material is not edited through a single object. */
+fn properties(config: &Config, object: ObjectRef) -> &'static [Property] {
+    let (properties, color): (&'static [Property], _) = match object {
+        ObjectRef::Sphere(i) => (&SPHERE_PROPERTIES, config.spheres[i].color),
+        ObjectRef::Plane(i) => (&PLANE_PROPERTIES, config.planes[i].color)
+    };
+    match color {
+        Some(_) => properties,
+        None => &properties[..properties.len() - 3]
+    }
+}
+
+fn get_property(config: &Config, object: ObjectRef, property: Property) -> Real {
+    let (origin, color) = match object {
+        ObjectRef::Sphere(i) => (config.spheres[i].origin, config.spheres[i].color),
+        ObjectRef::Plane(i) => (config.planes[i].origin, config.planes[i].color)
+    };
+    match (object, property, color) {
+        (_, Property::X, _) => origin.x,
+        (_, Property::Y, _) => origin.y,
+        (_, Property::Z, _) => origin.z,
+        (_, Property::Red, Some(color)) => color.r as Real,
+        (_, Property::Green, Some(color)) => color.g as Real,
+        (_, Property::Blue, Some(color)) => color.b as Real,
+        (ObjectRef::Sphere(i), Property::Radius, _) => config.spheres[i].radius,
+        (ObjectRef::Plane(i), Property::NormalX, _) => config.planes[i].orient.x,
+        (ObjectRef::Plane(i), Property::NormalY, _) => config.planes[i].orient.y,
+        (ObjectRef::Plane(i), Property::NormalZ, _) => config.planes[i].orient.z,
+        _ => unreachable!("{:?} has no property {:?}", object, property)
+    }
+}
+
+fn adjust_property(config: &mut Config, object: ObjectRef, property: Property, delta: Real) {
+    let (origin, color) = match object {
+        ObjectRef::Sphere(i) => {
+            let sphere = &mut config.spheres[i];
+            (&mut sphere.origin, sphere.color.as_mut())
+        },
+        ObjectRef::Plane(i) => {
+            let plane = &mut config.planes[i];
+            (&mut plane.origin, plane.color.as_mut())
+        }
+    };
+    match (property, color) {
+        (Property::X, _) => origin.x += delta,
+        (Property::Y, _) => origin.y += delta,
+        (Property::Z, _) => origin.z += delta,
+        (Property::Red, Some(color)) => color.r = (color.r + to_f32(delta)).clamp(0.0, 255.0),
+        (Property::Green, Some(color)) => color.g = (color.g + to_f32(delta)).clamp(0.0, 255.0),
+        (Property::Blue, Some(color)) => color.b = (color.b + to_f32(delta)).clamp(0.0, 255.0),
+        _ => ()
+    }
+
+    match (object, property) {
+        (ObjectRef::Sphere(i), Property::Radius) => {
+            let sphere = &mut config.spheres[i];
+            sphere.radius = (sphere.radius + delta).max(0.05);
+        },
+        (ObjectRef::Plane(i), Property::NormalX | Property::NormalY | Property::NormalZ) => {
+            // Tilt the normal and keep it unit length. A normal that
+            // would collapse to zero is left untouched.
+            let plane = &mut config.planes[i];
+            let mut orient = plane.orient;
+            match property {
+                Property::NormalX => orient.x += delta,
+                Property::NormalY => orient.y += delta,
+                _ => orient.z += delta
+            }
+            if orient.magnitude() > 1e-6 {
+                plane.orient = orient.normalise();
+            }
+        },
+        _ => ()
+    }
+}
+
+
+// The size of the property panel
+const PANEL_WIDTH: i32 = 220;
+const LINE_HEIGHT: i32 = 20;
+
+pub struct Editor {
+    // Keeps track of the object picked in the viewer and which of its
+    // properties is being edited. TAB moves to the next property, the
+    // plus and minus keys change its value.
+    pub selected: Option<ObjectRef>,
+    property: usize
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self { selected: None, property: 0 }
+    }
+
+    /* Select an object, or nothing. Returns true if the selection changed. */
+    pub fn select(&mut self, object: Option<ObjectRef>) -> bool {
+        if self.selected == object {
+            return false;
+        }
+        self.selected = object;
+        self.property = 0;
+        true
+    }
+
+    /* Apply this frame's input to the selected object, returns true if the config changed. */
+    pub fn update(&mut self, rl: &RaylibHandle, config: &mut Config) -> bool {
+        let Some(object) = self.selected else {
+            return false;
+        };
+        let properties = properties(config, object);
+
+        if rl.is_key_pressed(KeyboardKey::KEY_TAB) {
+            self.property = (self.property + 1) % properties.len();
+        }
+
+        let property = properties[self.property];
+        let increase = rl.is_key_pressed(KeyboardKey::KEY_EQUAL) || rl.is_key_pressed(KeyboardKey::KEY_KP_ADD);
+        let decrease = rl.is_key_pressed(KeyboardKey::KEY_MINUS) || rl.is_key_pressed(KeyboardKey::KEY_KP_SUBTRACT);
+        match (increase, decrease) {
+            (true, false) => adjust_property(config, object, property, property.step()),
+            (false, true) => adjust_property(config, object, property, -property.step()),
+            _ => return false
+        }
+        true
+    }
+
+    /* True while one of the color channels is the property being edited. */
+    pub fn editing_color(&self, config: &Config) -> bool {
+        self.selected.is_some_and(|object| matches!(
+            properties(config, object)[self.property],
+            Property::Red | Property::Green | Property::Blue
+        ))
+    }
+
+    /* The position and size of the property panel on a screen of the given width, None when nothing is selected. */
+    fn panel(&self, config: &Config, screen_width: i32) -> Option<(i32, i32, i32, i32)> {
+        let object = self.selected?;
+        let height = LINE_HEIGHT * (properties(config, object).len() as i32 + 2) + 10;
+        Some((screen_width - PANEL_WIDTH - 10, 10, PANEL_WIDTH, height))
+    }
+
+    /* True when the point lies on the property panel, clicks there are not meant for the scene behind it. */
+    pub fn covers(&self, config: &Config, screen_width: i32, x: f32, y: f32) -> bool {
+        self.panel(config, screen_width).is_some_and(|(left, top, width, height)| {
+            x >= left as f32 && x < (left + width) as f32 && y >= top as f32 && y < (top + height) as f32
+        })
+    }
+
+    /* Draw a panel listing the properties of the selected object in the upper right corner. */
+    pub fn draw(&self, d: &mut RaylibDrawHandle, config: &Config) {
+        let (Some(object), Some((x, y, width, height))) = (self.selected, self.panel(config, d.get_screen_width())) else {
+            return;
+        };
+        let properties = properties(config, object);
+        let (font_size, line_height) = (16, LINE_HEIGHT);
+
+        d.draw_rectangle(x, y, width, height, Color::new(0, 0, 0, 180));
+        d.draw_rectangle_lines(x, y, width, height, Color::YELLOW);
+
+        let (title, material) = match object {
+            ObjectRef::Sphere(i) => (format!("sphere {}", i), &config.spheres[i].material),
+            ObjectRef::Plane(i) => (format!("plane {}", i), &config.planes[i].material)
+        };
+        let title = match material {
+            Some(material) => format!("{} ({})", title, material),
+            None => title
+        };
+        d.draw_text(&title, x + 10, y + 10, font_size, Color::YELLOW);
+
+        for (n, property) in properties.iter().enumerate() {
+            let marker = if n == self.property { ">" } else { " " };
+            let line = format!("{} {:<9} {:>8.2}", marker, property.name(), get_property(config, object, *property));
+            let color = if n == self.property { Color::YELLOW } else { Color::WHITE };
+            d.draw_text(&line, x + 10, y + 10 + line_height * (n as i32 + 1), font_size, color);
+        }
+        d.draw_text("TAB next  +/- change", x + 10, y + 10 + line_height * (properties.len() as i32 + 1), font_size, Color::GRAY);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::color::RGBAColor;
+    use crate::material::Surface;
+    use crate::normal::Normal3;
+    use crate::plane::Plane;
+    use crate::point::Point3;
+    use crate::real::TOLERANCE;
+    use crate::schema::CURRENT_VERSION;
+    use crate::sphere::Sphere;
+
+    fn config() -> Config {
+        let color = RGBAColor::new(250.0, 0.0, 0.0).unwrap();
+        Config {
+            version: CURRENT_VERSION.to_string(),
+            spheres: vec![Sphere{
+                origin: Point3::new(0.0, 0.0, -5.0), radius: 1.0,
+                color: Some(color), material: None, surface: Surface::Color(color)
+            }],
+            planes: vec![Plane{
+                origin: Point3::new(0.0, -1.0, 0.0), orient: Normal3::new(0.0, 1.0, 0.0),
+                color: Some(color), material: None, one_sided: false, surface: Surface::Color(color)
+            }],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_adjust_sphere() {
+        let mut config = config();
+        let sphere = ObjectRef::Sphere(0);
+        adjust_property(&mut config, sphere, Property::Radius, 0.5);
+        adjust_property(&mut config, sphere, Property::Red, 20.0);
+
+        assert!((get_property(&config, sphere, Property::Radius) - 1.5).abs() < TOLERANCE);
+        // colors are clamped to the valid range
+        assert_eq!(get_property(&config, sphere, Property::Red), 255.0);
+    }
+
+    #[test]
+    fn test_adjust_plane_normal_stays_normalised() {
+        let mut config = config();
+        adjust_property(&mut config, ObjectRef::Plane(0), Property::NormalX, 1.0);
+
+        assert!((config.planes[0].orient.magnitude() - 1.0).abs() < TOLERANCE);
+        assert!(config.planes[0].orient.x > 0.0);
+    }
+
+    #[test]
+    fn test_panel_covers_clicks_and_tracks_color_edits() {
+        let config = config();
+        let mut editor = Editor::new();
+        assert!(!editor.covers(&config, 1024, 900.0, 20.0));
+
+        editor.select(Some(ObjectRef::Sphere(0)));
+        assert!(editor.covers(&config, 1024, 900.0, 20.0));
+        assert!(!editor.covers(&config, 1024, 100.0, 20.0));
+        assert!(!editor.editing_color(&config));
+        editor.property = 4;
+        assert!(editor.editing_color(&config));
+    }
+}
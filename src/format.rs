@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    // The languages a scene file can be written in. They all describe
+    // the same schema, the format is picked by the file extension.
+    Json,
+    Toml,
+    Yaml,
+    Ron
+}
+
+#[derive(Debug)]
+pub struct UnknownFormatError(String);
+
+impl fmt::Display for UnknownFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cannot tell the format of '{}', use a .json, .toml, .yaml or .ron file.", self.0)
+    }
+}
+
+impl Error for UnknownFormatError {}
+
+impl SceneFormat {
+    pub fn from_path(path: &Path) -> Result<Self, UnknownFormatError> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match extension.to_lowercase().as_str() {
+            "json" => Ok(SceneFormat::Json),
+            "toml" => Ok(SceneFormat::Toml),
+            "yaml" | "yml" => Ok(SceneFormat::Yaml),
+            "ron" => Ok(SceneFormat::Ron),
+            _ => Err(UnknownFormatError(path.display().to_string()))
+        }
+    }
+
+    /* Read a scene file into a generic tree, so every format goes through the same migrations. */
+    pub fn parse(&self, source: &str) -> Result<Value, Box<dyn Error>> {
+        Ok(match self {
+            SceneFormat::Json => serde_json::from_str(source)?,
+            SceneFormat::Toml => toml::from_str(source)?,
+            SceneFormat::Yaml => serde_yaml::from_str(source)?,
+            SceneFormat::Ron => ron::from_str(source)?
+        })
+    }
+
+    /* Write a scene, JSON is indented by four spaces like the example scenes. Every format ends with a newline. */
+    pub fn write<T: Serialize>(&self, scene: &T) -> Result<String, Box<dyn Error>> {
+        let mut written = match self {
+            SceneFormat::Json => {
+                let mut out = Vec::new();
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+                scene.serialize(&mut serde_json::Serializer::with_formatter(&mut out, formatter))?;
+                String::from_utf8(out)?
+            },
+            SceneFormat::Toml => toml::to_string_pretty(scene)?,
+            SceneFormat::Yaml => serde_yaml::to_string(scene)?,
+            SceneFormat::Ron => {
+                // RON writes structs as (field: value), which cannot be read back
+                // without knowing the type. Going through a JSON tree writes maps.
+                let tree = serde_json::to_value(scene)?;
+                ron::ser::to_string_pretty(&tree, ron::ser::PrettyConfig::default())?
+            }
+        };
+        if !written.ends_with('\n') {
+            written.push('\n');
+        }
+        Ok(written)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_every_format_round_trips() {
+        let scene = json!({
+            "version": "1.0",
+            "spheres": [{ "origin": { "x": 0.0, "y": 0.0, "z": -5.0 }, "radius": 1.5, "material": "red" }],
+            "materials": { "red": { "color": { "r": 255.0, "g": 0.0, "b": 0.0, "a": 255.0 } } }
+        });
+        for format in [SceneFormat::Json, SceneFormat::Toml, SceneFormat::Yaml, SceneFormat::Ron] {
+            let written = format.write(&scene).unwrap();
+            assert_eq!(format.parse(&written).unwrap(), scene, "{:?}", format);
+        }
+        assert_eq!(SceneFormat::from_path(Path::new("scene.YML")).unwrap(), SceneFormat::Yaml);
+        assert!(SceneFormat::from_path(Path::new("scene.txt")).is_err());
+    }
+}
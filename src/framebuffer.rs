@@ -0,0 +1,158 @@
+use crate::color::RGBAColor;
+
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PixelStats {
+    // Running statistics for all samples taken within a single pixel.
+    // The color is kept as a running mean so the pixel can be shown at
+    // any moment. The variance is tracked over the luminance of the
+    // samples using Welford's online algorithm.
+    pub samples: u32,
+    mean: [f32; 3],
+    luminance_mean: f32,
+    luminance_m2: f32
+}
+
+impl PixelStats {
+
+    pub fn add_sample(&mut self, color: RGBAColor) {
+        self.samples += 1;
+        let n = self.samples as f32;
+
+        let rgb = [color.r, color.g, color.b];
+        for (mean, value) in self.mean.iter_mut().zip(rgb) {
+            *mean += (value - *mean) / n;
+        }
+
+        let luminance = luminance(color);
+        let delta = luminance - self.luminance_mean;
+        self.luminance_mean += delta / n;
+        self.luminance_m2 += delta * (luminance - self.luminance_mean);
+    }
+
+    /* Sample variance of the luminance, 0 until at least two samples are taken. */
+    pub fn variance(&self) -> f32 {
+        if self.samples < 2 {
+            0.0
+        } else {
+            self.luminance_m2 / (self.samples - 1) as f32
+        }
+    }
+
+    /* Estimated error of the mean luminance, normalised to the [0, 1] range. */
+    pub fn standard_error(&self) -> f32 {
+        if self.samples == 0 {
+            return f32::INFINITY;
+        }
+        (self.variance() / self.samples as f32).sqrt() / 255.0
+    }
+
+    pub fn color(&self) -> RGBAColor {
+        let [r, g, b] = self.mean;
+        RGBAColor { r, g, b, a: 255.0 }
+    }
+}
+
+/* Rec. 709 luminance of a color */
+fn luminance(color: RGBAColor) -> f32 {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b
+}
+
+
+pub struct Framebuffer {
+    // The framebuffer holds the accumulated samples for every pixel
+    // of the image, stored row by row starting at the upper left.
+    pub width: i32,
+    pub height: i32,
+    pixels: Vec<PixelStats>,
+    total_samples: u64
+}
+
+impl Framebuffer {
+    pub fn new(width: i32, height: i32) -> Self {
+        let pixels = vec![PixelStats::default(); (width * height) as usize];
+        Self { width, height, pixels, total_samples: 0 }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> &PixelStats {
+        &self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn add_sample(&mut self, x: i32, y: i32, color: RGBAColor) {
+        self.pixels[(y * self.width + x) as usize].add_sample(color);
+        self.total_samples += 1;
+    }
+
+    /* Average amount of samples accumulated per pixel */
+    pub fn samples_per_pixel(&self) -> f64 {
+        self.total_samples as f64 / self.pixels.len() as f64
+    }
+
+    /* Throw away all accumulated samples, e.g. after the camera moved. */
+    pub fn clear(&mut self) {
+        self.pixels.fill(PixelStats::default());
+        self.total_samples = 0;
+    }
+
+    /* Write the current image as tightly packed 8-bit RGBA into out,
+    which should hold exactly 4 bytes for every pixel. */
+    pub fn write_rgba(&self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.pixels.len() * 4);
+        for (p, rgba) in self.pixels.iter().zip(out.chunks_exact_mut(4)) {
+            let (r, g, b) = p.color().as_rgb_tuple();
+            rgba.copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+
+    pub fn as_rgb_tuples(&self) -> Vec<(u8, u8, u8)> {
+        self.pixels
+            .iter()
+            .map(|p| p.color().as_rgb_tuple())
+            .collect()
+    }
+
+    /* Visualise the amount of samples spent per pixel going from
+    blue (min_spp or less) to red (max_spp). */
+    pub fn samples_heat_map(&self, min_spp: u32, max_spp: u32) -> Vec<(u8, u8, u8)> {
+        let range = max_spp.saturating_sub(min_spp).max(1) as f32;
+        self.pixels
+            .iter()
+            .map(|p| {
+                let a = p.samples.saturating_sub(min_spp) as f32 / range;
+                RGBAColor::heat(a).as_rgb_tuple()
+            })
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_constant_samples_have_no_variance() {
+        let mut stats = PixelStats::default();
+        let color = RGBAColor::new(10.0, 20.0, 30.0).unwrap();
+        for _ in 0..8 {
+            stats.add_sample(color);
+        }
+
+        assert_eq!(stats.samples, 8);
+        assert!(stats.variance().abs() < 1e-3);
+        assert!((stats.color().g - 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_variance_of_black_and_white() {
+        let mut stats = PixelStats::default();
+        stats.add_sample(RGBAColor::new(0.0, 0.0, 0.0).unwrap());
+        stats.add_sample(RGBAColor::new(255.0, 255.0, 255.0).unwrap());
+
+        // luminance of white is 255, so the mean is 127.5 and the
+        // sample variance (255 - 127.5)^2 * 2 / 1
+        assert!((stats.color().r - 127.5).abs() < 1e-3);
+        assert!((stats.variance() - 32512.5).abs() < 1.0);
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use gltf::camera::Projection;
+use gltf::image::Format;
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+
+use crate::camera::Camera;
+use crate::color::RGBAColor;
+use crate::config::Config;
+use crate::light::Light;
+use crate::material::{Image, Material, Texture};
+use crate::matrix::Mat4;
+use crate::mesh::Mesh;
+use crate::normal::Normal3;
+use crate::point::Point3;
+use crate::real::Real;
+use crate::schema::CURRENT_VERSION;
+use crate::transform::Transform;
+use crate::vec::Vec3;
+
+
+#[derive(Debug)]
+pub struct GltfError(String);
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cannot import glTF file: {}", self.0)
+    }
+}
+
+impl Error for GltfError {}
+
+pub fn is_gltf(path: &Path) -> bool {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    matches!(extension.to_lowercase().as_str(), "gltf" | "glb")
+}
+
+
+fn to_color(c: [f32; 4]) -> RGBAColor {
+    RGBAColor { r: c[0] * 255.0, g: c[1] * 255.0, b: c[2] * 255.0, a: c[3] * 255.0 }
+}
+
+/* Convert a decoded glTF image to the texels of an image texture. */
+fn to_image(image: &gltf::image::Data) -> Image {
+    let (channels, size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4)
+    };
+    let texels = image.pixels.chunks_exact(channels * size).map(|texel| {
+        let value = |channel: usize| {
+            let bytes = &texel[channel * size..(channel + 1) * size];
+            match size {
+                1 => bytes[0] as f32 / 255.0,
+                2 => u16::from_ne_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+                _ => f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).clamp(0.0, 1.0)
+            }
+        };
+        to_color(match channels {
+            1 => [value(0), value(0), value(0), 1.0],
+            2 => [value(0), value(0), value(0), value(1)],
+            3 => [value(0), value(1), value(2), 1.0],
+            _ => [value(0), value(1), value(2), value(3)]
+        })
+    }).collect();
+    Image { width: image.width as usize, height: image.height as usize, texels }
+}
+
+/* Read one of the images of a glTF file, for an image texture that refers to it. Only that image is decoded,
+and the buffers are only read when the image is stored in one. */
+pub fn read_image(path: &Path, index: usize) -> Result<Image, Box<dyn Error>> {
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+    let image = document.images().nth(index).ok_or_else(|| GltfError(format!("{} has no image {}", path.display(), index)))?;
+    let base = path.parent().unwrap_or(Path::new("./"));
+    let buffers = match image.source() {
+        gltf::image::Source::View { .. } => gltf::import_buffers(&document, Some(base), blob)?,
+        gltf::image::Source::Uri { .. } => Vec::new()
+    };
+    Ok(to_image(&gltf::image::Data::from_source(image.source(), Some(base), &buffers)?))
+}
+
+
+struct Importer {
+    // Walks the node tree of a glTF scene and collects everything in a
+    // scene config. glTF materials are stored under their name, or
+    // under "material<index>" when they have none. The images become
+    // textures named "image<index>" that refer back to the file by its
+    // name, so a converted scene has to stay next to the glTF file.
+    file_name: PathBuf,
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+    material_names: HashMap<Option<usize>, String>,
+    scene: Config
+}
+
+impl Importer {
+    fn material(&mut self, material: &gltf::Material) -> String {
+        if let Some(name) = self.material_names.get(&material.index()) {
+            return name.clone();
+        }
+        let base = match (material.name(), material.index()) {
+            (Some(name), _) => name.to_string(),
+            (None, Some(index)) => format!("material{}", index),
+            (None, None) => "default".to_string()
+        };
+        // Materials can share a name, the later ones get a number added
+        // until the name is free.
+        let mut name = base.clone();
+        let mut count = 1;
+        while self.scene.materials.contains_key(&name) {
+            name = format!("{}{}", base, count);
+            count += 1;
+        }
+
+        // A textured material shows the image as it is, without the
+        // base color factor.
+        let pbr = material.pbr_metallic_roughness();
+        let texture = pbr.base_color_texture().map(|info| self.texture(info.texture().source().index()));
+        self.scene.materials.insert(name.clone(), Material {
+            color: texture.is_none().then(|| to_color(pbr.base_color_factor())),
+            texture,
+            metallic: Some(pbr.metallic_factor() as Real),
+            roughness: Some(pbr.roughness_factor() as Real)
+        });
+        self.material_names.insert(material.index(), name.clone());
+        name
+    }
+
+    fn texture(&mut self, index: usize) -> String {
+        let name = format!("image{}", index);
+        if !self.scene.textures.contains_key(&name) {
+            let image = self.images.get(index).map(to_image).unwrap_or_default();
+            self.scene.textures.insert(name.clone(), Texture::Image {
+                path: self.file_name.clone(),
+                index: Some(index),
+                image: Arc::new(image)
+            });
+        }
+        name
+    }
+
+    fn primitive(&mut self, primitive: &gltf::Primitive, world: &Transform) {
+        // Points and lines have no surface to hit
+        if primitive.mode() != Mode::Triangles {
+            return;
+        }
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let Some(positions) = reader.read_positions() else {
+            return;
+        };
+        let vertices: Vec<Point3> = positions.map(|[x, y, z]| world.point(&Point3::new(x as Real, y as Real, z as Real))).collect();
+        let normals: Vec<Normal3> = reader.read_normals()
+            .map(|normals| normals.map(|[x, y, z]| world.normal(&Normal3::new(x as Real, y as Real, z as Real))).collect())
+            .unwrap_or_default();
+
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..vertices.len()).collect()
+        };
+        // A mirroring transform turns the triangles inside out, swap two
+        // corners to keep them counter clockwise.
+        let mirrored = world.swaps_handedness();
+        let triangles = indices.chunks_exact(3)
+            .map(|t| if mirrored { [t[0], t[2], t[1]] } else { [t[0], t[1], t[2]] })
+            .collect();
+
+        // The base color texture is looked up at the texture coordinates,
+        // without one the vertex colors are blended with the base color.
+        let material = primitive.material();
+        let pbr = material.pbr_metallic_roughness();
+        let uvs = pbr.base_color_texture()
+            .and_then(|info| reader.read_tex_coords(info.tex_coord()))
+            .map(|uvs| uvs.into_f32().map(|[u, v]| [u as Real, v as Real]).collect())
+            .unwrap_or_default();
+        let factor = pbr.base_color_factor();
+        let colors = match (reader.read_colors(0), pbr.base_color_texture()) {
+            (Some(colors), None) => colors.into_rgba_f32().map(|c| to_color([0, 1, 2, 3].map(|n| factor[n] * c[n]))).collect(),
+            _ => Vec::new()
+        };
+
+        let material = self.material(&material);
+        self.scene.meshes.push(Mesh {
+            vertices,
+            triangles,
+            normals,
+            colors,
+            uvs,
+            material: Some(material),
+            ..Mesh::default()
+        });
+    }
+
+    fn node(&mut self, node: &gltf::Node, parent: &Transform) {
+        let local = Mat4::from_columns(node.transform().matrix().map(|c| c.map(|v| v as Real)));
+        // A node scaled to nothing cannot be seen, neither can its children
+        let Some(local) = Transform::new(local) else {
+            let name = node.name().map_or_else(|| node.index().to_string(), |name| format!("\"{}\"", name));
+            self.scene.warnings.push(format!("node {} is scaled to nothing and left out with its children", name));
+            return;
+        };
+        let world = *parent * local;
+        let position = world.point(&Point3::new(0.0, 0.0, 0.0));
+        // Cameras and lights look down their local negative z axis
+        let forward = world.direction(&Vec3::new(0.0, 0.0, -1.0)).normalise();
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.primitive(&primitive, &world);
+            }
+        }
+        if let (Some(camera), None) = (node.camera(), &self.scene.camera) {
+            let vfov = match camera.projection() {
+                Projection::Perspective(perspective) => (perspective.yfov() as Real).to_degrees(),
+                Projection::Orthographic(_) => 90.0
+            };
+            let mut camera = Camera { vfov, ..Camera::new(position) };
+            camera.look_along(forward);
+            self.scene.camera = Some(camera);
+        }
+        if let Some(light) = node.light() {
+            let c = light.color();
+            let color = RGBAColor { r: c[0] * 255.0, g: c[1] * 255.0, b: c[2] * 255.0, a: 255.0 };
+            let intensity = light.intensity() as Real;
+            self.scene.lights.push(match light.kind() {
+                Kind::Point => Light::Point { position, color, intensity },
+                Kind::Directional => Light::Directional { direction: forward, color, intensity },
+                Kind::Spot { inner_cone_angle, outer_cone_angle } => Light::Spot {
+                    position,
+                    direction: forward,
+                    color,
+                    intensity,
+                    inner_angle: inner_cone_angle as Real,
+                    outer_angle: outer_cone_angle as Real
+                }
+            });
+        }
+
+        for child in node.children() {
+            self.node(&child, &world);
+        }
+    }
+}
+
+/* Read a .gltf or .glb file as a scene. The node transforms are applied to the meshes, the first camera found becomes the scene camera. */
+pub fn import_gltf(path: &Path) -> Result<Config, Box<dyn Error>> {
+    let (document, buffers, images) = gltf::import(path)?;
+    let scene = document.default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| GltfError(format!("{} has no scene", path.display())))?;
+
+    let mut importer = Importer {
+        file_name: PathBuf::from(path.file_name().unwrap_or_default()),
+        buffers,
+        images,
+        material_names: HashMap::new(),
+        scene: Config { version: CURRENT_VERSION.to_string(), ..Config::default() }
+    };
+    for node in scene.nodes() {
+        importer.node(&node, &Transform::IDENTITY);
+    }
+    Ok(importer.scene)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    use crate::ray::Ray;
+    use crate::real::TOLERANCE;
+
+    // A single triangle with corners (0,0,0), (1,0,0) and (0,1,0), moved
+    // up by a parent node and scaled by its own node. The second node
+    // holds a camera and the third a point light. The last node is
+    // scaled to nothing and cannot be shown.
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [ { "nodes": [0, 2, 3, 4] } ],
+        "nodes": [
+            { "translation": [0.0, 2.0, 0.0], "children": [1] },
+            { "mesh": 0, "scale": [2.0, 2.0, 2.0] },
+            { "camera": 0, "translation": [0.0, 0.0, 5.0] },
+            { "extensions": { "KHR_lights_punctual": { "light": 0 } }, "translation": [1.0, 1.0, 1.0] },
+            { "name": "hidden", "mesh": 0, "scale": [0.0, 0.0, 0.0] }
+        ],
+        "cameras": [ { "type": "perspective", "perspective": { "yfov": 1.0, "znear": 0.1 } } ],
+        "extensionsUsed": [ "KHR_lights_punctual" ],
+        "extensions": { "KHR_lights_punctual": { "lights": [ { "type": "point", "color": [1.0, 0.5, 0.0], "intensity": 3.0 } ] } },
+        "meshes": [ { "primitives": [ { "attributes": { "POSITION": 0 }, "material": 0 } ] } ],
+        "materials": [ { "name": "orange", "pbrMetallicRoughness": { "baseColorFactor": [1.0, 0.5, 0.0, 1.0], "metallicFactor": 0.25 } } ],
+        "accessors": [ { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] } ],
+        "bufferViews": [ { "buffer": 0, "byteLength": 36 } ],
+        "buffers": [ { "byteLength": 36, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA" } ]
+    }"#;
+
+    // The same triangle with texture coordinates, its material has a
+    // two texel texture: red on the left and blue on the right.
+    const TEXTURED: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [ { "nodes": [0] } ],
+        "nodes": [ { "mesh": 0 } ],
+        "meshes": [ { "primitives": [ { "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": 0 } ] } ],
+        "materials": [ { "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } } ],
+        "textures": [ { "source": 0 } ],
+        "images": [ { "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAIAAAB7QOjdAAAADUlEQVR4nGP4zwAE/wEHAAH/4iOeWQAAAABJRU5ErkJggg==" } ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }
+        ],
+        "bufferViews": [ { "buffer": 0, "byteLength": 36 }, { "buffer": 0, "byteOffset": 36, "byteLength": 24 } ],
+        "buffers": [ { "byteLength": 60, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAACAPgAAAD8AAEA/AAAAPwAAgD4AAAA/" } ]
+    }"#;
+
+    #[test]
+    fn test_materials_with_the_same_name_are_kept_apart() {
+        // The third material would take the name of the second one when
+        // its index is added to its name.
+        let gltf = r#"{
+            "asset": { "version": "2.0" },
+            "scenes": [ { "nodes": [0] } ],
+            "nodes": [ { "mesh": 0 } ],
+            "meshes": [ { "primitives": [
+                { "attributes": { "POSITION": 0 }, "material": 0 },
+                { "attributes": { "POSITION": 0 }, "material": 1 },
+                { "attributes": { "POSITION": 0 }, "material": 2 }
+            ] } ],
+            "materials": [ { "name": "red" }, { "name": "red2" }, { "name": "red" } ],
+            "accessors": [ { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] } ],
+            "bufferViews": [ { "buffer": 0, "byteLength": 36 } ],
+            "buffers": [ { "byteLength": 36, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA" } ]
+        }"#;
+        let path = std::env::temp_dir().join(format!("raytracer_gltf_materials_test_{}.gltf", std::process::id()));
+        fs::write(&path, gltf).unwrap();
+        let scene = import_gltf(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let names: Vec<&str> = scene.meshes.iter().map(|mesh| mesh.material.as_deref().unwrap()).collect();
+        assert_eq!(names, vec!["red", "red2", "red1"]);
+        assert_eq!(scene.materials.len(), 3);
+    }
+
+    #[test]
+    fn test_textures_are_sampled_at_texture_coordinates() {
+        let path = std::env::temp_dir().join(format!("raytracer_gltf_texture_test_{}.gltf", std::process::id()));
+        fs::write(&path, TEXTURED).unwrap();
+        let scene = import_gltf(&path).unwrap();
+        let read = read_image(&path, 0).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(scene.meshes[0].uvs, vec![[0.25, 0.5], [0.75, 0.5], [0.25, 0.5]]);
+        assert!(scene.meshes[0].colors.is_empty());
+        assert_eq!(scene.materials["material0"].texture.as_deref(), Some("image0"));
+        let Texture::Image { path: file, index, .. } = &scene.textures["image0"] else {
+            panic!("expected an image texture");
+        };
+        assert_eq!((file.as_os_str(), *index), (path.file_name().unwrap(), Some(0)));
+        assert_eq!((read.width, read.height), (2, 1));
+
+        let mesh = &scene.intersectables()[0];
+        let color_under = |x: Real, y: Real| {
+            let hit = mesh.intersect_hits(&Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0))).unwrap()[0];
+            mesh.color_at(&hit)
+        };
+        let (left, right) = (color_under(0.1, 0.1), color_under(0.6, 0.1));
+        assert_eq!((left.r, left.b), (255.0, 0.0));
+        assert_eq!((right.r, right.b), (0.0, 255.0));
+    }
+
+    #[test]
+    fn test_import_applies_node_transforms() {
+        let path = std::env::temp_dir().join(format!("raytracer_gltf_test_{}.gltf", std::process::id()));
+        fs::write(&path, TRIANGLE).unwrap();
+        let scene = import_gltf(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(scene.warnings, vec!["node \"hidden\" is scaled to nothing and left out with its children"]);
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+        assert_eq!((mesh.vertices[1].x, mesh.vertices[1].y), (2.0, 2.0));
+        assert_eq!((mesh.vertices[2].x, mesh.vertices[2].y), (0.0, 4.0));
+
+        let material = &scene.materials[mesh.material.as_ref().unwrap()];
+        assert_eq!(material.color.unwrap().g, 127.5);
+        assert_eq!(material.metallic, Some(0.25));
+
+        let camera = scene.camera.unwrap();
+        assert_eq!(camera.location.z, 5.0);
+        assert!(camera.yaw.abs() < TOLERANCE && camera.pitch.abs() < TOLERANCE);
+        assert!((camera.vfov - (1.0 as Real).to_degrees()).abs() < 1e-4);
+
+        assert!(matches!(scene.lights[0], Light::Point { intensity, .. } if intensity == 3.0));
+    }
+}
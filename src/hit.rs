@@ -1,9 +1,32 @@
+use crate::normal::Normal3;
 use crate::point::Point3;
-use crate::vec::Vec3;
+use crate::ray::Ray;
+use crate::real::Real;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Hit {
-    pub t: f64,
+    pub t: Real,
     pub p: Point3,
-    pub normal: Vec3,
+    // The normal always faces the ray that made the hit. front_face
+    // tells whether that is the outward normal of the surface, it is
+    // false when the ray hit the inside or the back of the surface.
+    pub normal: Normal3,
+    pub front_face: bool,
+    // Where on the object the hit is. For meshes these are the index of
+    // the triangle and the barycentric coordinates of the hit on it, the
+    // other objects leave them at zero.
+    pub primitive: usize,
+    pub uv: (Real, Real)
+}
+
+impl Hit {
+    pub fn new(t: Real, p: Point3, normal: Normal3) -> Self {
+        Self { t, p, normal, front_face: true, primitive: 0, uv: (0.0, 0.0) }
+    }
+
+    /* A hit along the ray at t, with the outward normal of the surface turned against the ray. */
+    pub fn facing(ray: &Ray, t: Real, outward: Normal3) -> Self {
+        let front_face = outward.dot(&ray.direction) <= 0.0;
+        Self { front_face, ..Self::new(t, ray.at(t), if front_face { outward } else { -outward }) }
+    }
 }
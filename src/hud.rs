@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use raylib::prelude::*;
+
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::render::ShadingMode;
+
+
+// How long a status message stays on screen.
+const STATUS_DURATION: Duration = Duration::from_secs(3);
+
+pub struct Hud {
+    // The heads-up display shows render statistics on top of the image.
+    // The rays per second are smoothed over frames, otherwise the number
+    // changes too fast to read.
+    pub visible: bool,
+    rays_per_second: f64,
+    status: Option<(String, Instant)>
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Self { visible: true, rays_per_second: 0.0, status: None }
+    }
+
+    /* Record that a render pass shot the given amount of rays. */
+    pub fn record_pass(&mut self, rays: usize, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if seconds <= 0.0 {
+            return;
+        }
+        let current = rays as f64 / seconds;
+        self.rays_per_second = if self.rays_per_second == 0.0 {
+            current
+        } else {
+            0.9 * self.rays_per_second + 0.1 * current
+        };
+    }
+
+    /* Show a message, like the path of a saved screenshot, for a few seconds. */
+    pub fn set_status(&mut self, message: String) {
+        self.status = Some((message, Instant::now()));
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, framebuffer: &Framebuffer, camera: &Camera, mode: ShadingMode) {
+        if let Some((message, shown_at)) = &self.status {
+            if shown_at.elapsed() < STATUS_DURATION {
+                d.draw_text(message, 10, d.get_screen_height() - 60, 16, Color::YELLOW);
+            }
+        }
+
+        if !self.visible {
+            return;
+        }
+
+        let lines = [
+            format!("FPS      {}", d.get_fps()),
+            format!("samples  {:.0} spp", framebuffer.samples_per_pixel()),
+            format!("rays/s   {:.2} M", self.rays_per_second / 1e6),
+            format!("position {:.2} {:.2} {:.2}", camera.location.x, camera.location.y, camera.location.z),
+            format!("yaw {:.1}  pitch {:.1}  fov {:.0}", camera.yaw.to_degrees(), camera.pitch.to_degrees(), camera.vfov),
+            format!("mode     {}", mode.name())
+        ];
+
+        let (font_size, line_height) = (16, 20);
+        d.draw_rectangle(10, 10, 270, line_height * lines.len() as i32 + 10, Color::new(0, 0, 0, 160));
+        for (n, line) in lines.iter().enumerate() {
+            d.draw_text(line, 15, 15 + line_height * n as i32, font_size, Color::WHITE);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{is_positive, Config};
+use crate::instance::Shape;
+use crate::material::Texture;
+use crate::normal::Normal3;
+use crate::quaternion::Rotation;
+use crate::real::Real;
+use crate::schema::{SchemaError, Segment};
+use crate::transform::Frame;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Include {
+    // Merges another scene file into the including one. The path is
+    // relative to the including file. The objects are scaled, rotated
+    // and then moved, and the names of the materials and textures
+    // declared in the other file get the prefix so they cannot clash.
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<Rotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Real>
+}
+
+#[derive(Debug)]
+pub enum IncludeError {
+    // The files that include each other, the first file is repeated at the end.
+    Cycle(Vec<PathBuf>),
+    // Something went wrong in an included file.
+    InFile(PathBuf, Box<dyn Error>)
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncludeError::Cycle(files) => {
+                let files: Vec<String> = files.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "scene files include each other: {}", files.join(" -> "))
+            },
+            IncludeError::InFile(path, error) => write!(f, "in {}: {}", path.display(), error)
+        }
+    }
+}
+
+impl Error for IncludeError {}
+
+
+fn prefixed<T>(names: BTreeMap<String, T>, prefix: &str) -> BTreeMap<String, T> {
+    names.into_iter().map(|(name, value)| (format!("{}{}", prefix, name), value)).collect()
+}
+
+impl Include {
+    pub fn frame(&self) -> Frame {
+        Frame { translate: self.translate, rotate: self.rotate, scale: self.scale }
+    }
+}
+
+/* Apply the prefix and transform of an include to the scene it refers to. */
+pub fn apply(mut scene: Config, include: &Include) -> Config {
+    let prefix = &include.prefix;
+    let scale = include.scale.unwrap_or(1.0);
+    // The scale is checked to be positive before the include is applied.
+    let transform = include.frame().transform();
+    // Normals turn with the objects but keep their length
+    let turn = |normal: Normal3| transform.normal(&normal) * normal.magnitude();
+
+    for sphere in &mut scene.spheres {
+        sphere.origin = transform.point(&sphere.origin);
+        sphere.radius *= scale;
+        sphere.material = sphere.material.take().map(|name| format!("{}{}", prefix, name));
+    }
+    for plane in &mut scene.planes {
+        plane.origin = transform.point(&plane.origin);
+        plane.orient = turn(plane.orient);
+        plane.material = plane.material.take().map(|name| format!("{}{}", prefix, name));
+    }
+    for mesh in &mut scene.meshes {
+        for vertex in &mut mesh.vertices {
+            *vertex = transform.point(vertex);
+        }
+        mesh.normals = mesh.normals.iter().map(|n| turn(*n)).collect();
+        mesh.material = mesh.material.take().map(|name| format!("{}{}", prefix, name));
+    }
+    scene.lights = scene.lights.into_iter().map(|light| light.moved(&transform)).collect();
+    if let Some(camera) = &mut scene.camera {
+        camera.location = transform.point(&camera.location);
+        if include.rotate.is_some() {
+            camera.look_along(transform.direction(&camera.forward()));
+        }
+    }
+    // Shapes stay in their own space, the instances placing them move.
+    for shape in scene.shapes.values_mut() {
+        let material = match shape {
+            Shape::Sphere(sphere) => &mut sphere.material,
+            Shape::Mesh(mesh) => &mut mesh.material
+        };
+        *material = material.take().map(|name| format!("{}{}", prefix, name));
+    }
+    for placement in &mut scene.instances {
+        placement.shape = format!("{}{}", prefix, placement.shape);
+        placement.place_within(&include.frame());
+    }
+    for material in scene.materials.values_mut() {
+        material.texture = material.texture.take().map(|name| format!("{}{}", prefix, name));
+    }
+    // Images are found next to the included file
+    let directory = include.path.parent().unwrap_or(Path::new(""));
+    for texture in scene.textures.values_mut() {
+        match texture {
+            Texture::Checker { scale: cells, .. } => *cells *= scale,
+            Texture::Image { path, .. } => *path = directory.join(&*path),
+            Texture::Solid { .. } => ()
+        }
+    }
+    scene.materials = prefixed(scene.materials, prefix);
+    scene.textures = prefixed(scene.textures, prefix);
+    scene.shapes = prefixed(scene.shapes, prefix);
+    scene
+}
+
+/* Add the contents of an included scene, the index of the include is used to report name clashes. */
+pub fn merge(scene: &mut Config, included: Config, index: usize) -> Result<(), SchemaError> {
+    let at = vec![Segment::Key("include".to_string()), Segment::Index(index)];
+    let clash = |kind: &str, name: &str| SchemaError::new(
+        at.clone(),
+        format!("{} \"{}\" is declared in both files, use a prefix to tell them apart", kind, name)
+    );
+
+    for (name, material) in included.materials {
+        if scene.materials.contains_key(&name) {
+            return Err(clash("material", &name));
+        }
+        scene.materials.insert(name, material);
+    }
+    for (name, texture) in included.textures {
+        if scene.textures.contains_key(&name) {
+            return Err(clash("texture", &name));
+        }
+        scene.textures.insert(name, texture);
+    }
+    for (name, shape) in included.shapes {
+        if scene.shapes.contains_key(&name) {
+            return Err(clash("shape", &name));
+        }
+        scene.shapes.insert(name, shape);
+    }
+    scene.spheres.extend(included.spheres);
+    scene.planes.extend(included.planes);
+    scene.meshes.extend(included.meshes);
+    scene.instances.extend(included.instances);
+    scene.lights.extend(included.lights);
+    // The including scene decides where the camera goes, an included camera is only used when it has none.
+    scene.camera = scene.camera.or(included.camera);
+    scene.files.extend(included.files);
+    scene.warnings.extend(included.warnings);
+    Ok(())
+}
+
+/* Check the parts of an include that serde cannot, a scale has to be positive. */
+pub fn validate(include: &Include, index: usize) -> Result<(), SchemaError> {
+    let at = |field: &str| vec![Segment::Key("include".to_string()), Segment::Index(index), Segment::Key(field.to_string())];
+    match (include.scale, include.rotate) {
+        (Some(scale), _) if !is_positive(scale) => Err(SchemaError::new(at("scale"), format!("scale must be positive, got {}", scale))),
+        (_, Some(rotation)) => rotation.check().map_err(|message| SchemaError::new(at("rotate"), message)),
+        _ => Ok(())
+    }
+}
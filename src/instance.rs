@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bounds::{around, corners, hits_box};
+use crate::color::RGBAColor;
+use crate::hit::Hit;
+use crate::mesh::Mesh;
+use crate::point::Point3;
+use crate::quaternion::Rotation;
+use crate::ray::Ray;
+use crate::real::Real;
+use crate::sphere::Sphere;
+use crate::traits::Intersectable;
+use crate::transform::{Frame, Transform};
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Shape {
+    // Geometry declared once under a name and placed any number of
+    // times by instances. It is given in its own space, around the
+    // origin, and is not shown by itself.
+    Sphere(Sphere),
+    Mesh(Mesh)
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Placement {
+    // Where an instance of a shape goes. The shape is scaled, rotated
+    // and then moved like the objects of an include.
+    pub shape: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<Rotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Real>
+}
+
+impl Placement {
+    pub fn frame(&self) -> Frame {
+        Frame { translate: self.translate, rotate: self.rotate, scale: self.scale }
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.frame().transform()
+    }
+
+    /* Move the placement along with the parent it is placed within. */
+    pub fn place_within(&mut self, parent: &Frame) {
+        let Frame { translate, rotate, scale } = self.frame().within(parent);
+        (self.translate, self.rotate, self.scale) = (translate, rotate, scale);
+    }
+
+    /* A placement of a shape at a frame. */
+    pub fn at(shape: String, frame: Frame) -> Self {
+        Placement { shape, translate: frame.translate, rotate: frame.rotate, scale: frame.scale }
+    }
+}
+
+
+pub struct Instance {
+    // A shared object seen through a transform. Rays are brought into
+    // the space of the object and the hits are brought back out, so the
+    // object itself is stored once however many instances there are.
+    object: Arc<dyn Intersectable>,
+    transform: Transform,
+    bounds: Option<(Point3, Point3)>
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Intersectable>, transform: Transform) -> Self {
+        // The box of the object turned and moved is no longer aligned with
+        // the axes, the new box goes around its corners.
+        let bounds = object.bounding_box()
+            .and_then(|(min, max)| around(corners(&min, &max).map(|c| transform.point(&c))));
+        Self { object, transform, bounds }
+    }
+}
+
+impl Intersectable for Instance {
+
+    fn intersect(&self, ray: &Ray) -> bool {
+        match &self.bounds {
+            Some((min, max)) => hits_box(ray, min, max),
+            None => true
+        }
+    }
+
+    fn intersect_hits(&self, ray: &Ray) -> Option<Vec<Hit>> {
+        let inverse = self.transform.inverse();
+        let local = Ray::new(inverse.point(&ray.origin), inverse.direction(&ray.direction));
+        if !self.object.intersect(&local) {
+            return None;
+        }
+        let hits = self.object.intersect_hits(&local)?;
+        Some(hits.into_iter().map(|hit| {
+            // A scale stretches the distances along the ray, t is measured
+            // again along the ray in the world.
+            let p = self.transform.point(&hit.p);
+            let t = if hit.t.is_finite() { (p - ray.origin).dot(&ray.direction) } else { hit.t };
+            // Transforming keeps the normal facing the ray, so front_face holds as well.
+            Hit { t, p, normal: self.transform.normal(&hit.normal), ..hit }
+        }).collect())
+    }
+
+    fn bounding_box(&self) -> Option<(Point3, Point3)> {
+        self.bounds
+    }
+
+    fn color_at(&self, hit: &Hit) -> RGBAColor {
+        // Textures stick to the object, they are looked up in its own space.
+        let local = Hit { p: self.transform.inverse().point(&hit.p), ..*hit };
+        self.object.color_at(&local)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::material::Surface;
+    use crate::real::TOLERANCE;
+
+    #[test]
+    fn test_hits_are_brought_back_to_the_world() {
+        let color = RGBAColor::new(255.0, 0.0, 0.0).unwrap();
+        let sphere: Arc<dyn Intersectable> = Arc::new(Sphere {
+            origin: Point3::new(0.0, 0.0, 0.0), radius: 1.0, color: Some(color), material: None, surface: Surface::Color(color)
+        });
+        let placement = Placement { shape: "ball".to_string(), translate: Some(Vec3::new(0.0, 0.0, -10.0)), rotate: None, scale: Some(2.0) };
+        let instance = Instance::new(sphere.clone(), placement.transform());
+        let other = Instance::new(sphere, Transform::translate(Vec3::new(5.0, 0.0, 0.0)));
+
+        let (min, max) = instance.bounding_box().unwrap();
+        assert!((min.z + 12.0).abs() < TOLERANCE && (max.x - 2.0).abs() < TOLERANCE);
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(instance.intersect(&ray));
+        // the hits lie on a sphere with radius 2 around z = -10, at their distance along the ray
+        let center = Point3::new(0.0, 0.0, -10.0);
+        for hit in instance.intersect_hits(&ray).unwrap() {
+            assert!(((hit.p - center).magnitude() - 2.0).abs() < TOLERANCE);
+            assert!((ray.at(hit.t) - hit.p).magnitude() < TOLERANCE);
+            // the far hit is on the inside, its normal is turned towards the ray
+            let outward = (hit.p - center) / 2.0;
+            let expected = if hit.front_face { outward } else { -outward };
+            assert!((hit.normal.to_vec() - expected).magnitude() < TOLERANCE);
+            assert!(hit.normal.dot(&ray.direction) <= 0.0);
+        }
+        assert!(!other.intersect(&ray));
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::RGBAColor;
+use crate::point::Point3;
+use crate::real::Real;
+use crate::transform::Transform;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Light {
+    // Light sources in the scene. The renderer does not shade with them
+    // yet, they are read and saved so scenes imported from other tools
+    // keep them. Spot light angles are in radians measured from the
+    // direction of the light.
+    Point { position: Point3, color: RGBAColor, intensity: Real },
+    Directional { direction: Vec3, color: RGBAColor, intensity: Real },
+    Spot {
+        position: Point3,
+        direction: Vec3,
+        color: RGBAColor,
+        intensity: Real,
+        inner_angle: Real,
+        outer_angle: Real
+    }
+}
+
+impl Light {
+    /* Move the light with the objects around it. Directions are turned but keep their length. */
+    pub fn moved(self, transform: &Transform) -> Self {
+        let turn = |direction: Vec3| transform.direction(&direction).normalise() * direction.magnitude();
+        match self {
+            Light::Point { position, color, intensity } => {
+                Light::Point { position: transform.point(&position), color, intensity }
+            },
+            Light::Directional { direction, color, intensity } => {
+                Light::Directional { direction: turn(direction), color, intensity }
+            },
+            Light::Spot { position, direction, color, intensity, inner_angle, outer_angle } => {
+                Light::Spot { position: transform.point(&position), direction: turn(direction), color, intensity, inner_angle, outer_angle }
+            }
+        }
+    }
+}
@@ -1,13 +1,9 @@
 use std::{env, fmt};
-use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::error::Error;
-
-use serde::Deserialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use raylib::prelude::*;
-use itertools::{iproduct, Itertools};
 
 /*
 Goal of this Project is to build a RayTracer. A raytracer is a program
@@ -38,41 +34,57 @@ Components:
 */
 
 mod sphere;
+mod quadratic;
 mod plane;
 mod ppm;
 mod vec;
+mod real;
+mod matrix;
+mod transform;
+mod quaternion;
 mod point;
+mod normal;
 mod ray;
 mod hit;
+mod light;
+mod mesh;
+mod bounds;
+mod bvh;
+mod simd;
+mod packet;
+mod instance;
+mod node;
+mod ply;
+mod stl;
 mod color;
 mod traits;
+mod camera;
+mod framebuffer;
+mod render;
+mod screen;
+mod controls;
+mod config;
+mod format;
+mod gltf_scene;
+mod include;
+mod material;
+mod editor;
+mod schema;
+mod watcher;
+mod hud;
 
-use sphere::Sphere;
-use plane::Plane;
-use vec::Vec3;
 use point::Point3;
-use ray::Ray;
-use color::RGBAColor;
-use traits::Intersectable;
-
-#[derive(Debug, Deserialize, Clone)]
-struct Config {
-    version: String,
-    spheres: Vec<Sphere>,
-    planes: Vec<Plane>
-}
-
-fn read_config_from_file<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
-    // Open the file in read-only mode with buffer.
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-
-    // Read the JSON contents of the file as an instance of `Config`.
-    let config: Config = serde_json::from_reader(reader)?;
-
-    // Return the `Config`
-    Ok(config)
-}
+use bvh::Bvh;
+use camera::{Camera, Viewport, Window};
+use framebuffer::Framebuffer;
+use render::{AdaptiveSampling, Shading, ShadingMode};
+use screen::Screen;
+use controls::{CameraController, KeyBindings};
+use config::{convert_scene_file, read_config_from_file};
+use format::SceneFormat;
+use editor::Editor;
+use watcher::ConfigWatcher;
+use hud::Hud;
 
 #[derive(Debug, Clone)]
 struct ArgumentError;
@@ -86,178 +98,284 @@ impl fmt::Display for ArgumentError {
 impl Error for ArgumentError {}
 
 
-struct Window {
-    // The window represents the screen that shows all pixels.
-    // It is created by providing a width. The aspect ratio is 
-    // used to calculate a proper height
-    pub width: i32,
-    pub height: i32
+/* Switch the shading mode with the number keys, 1 selects the first mode. */
+fn shading_mode_on_key_press(rl: &RaylibHandle) -> Option<ShadingMode> {
+    let keys = [
+        KeyboardKey::KEY_ONE, KeyboardKey::KEY_TWO, KeyboardKey::KEY_THREE,
+        KeyboardKey::KEY_FOUR, KeyboardKey::KEY_FIVE, KeyboardKey::KEY_SIX
+    ];
+    keys.iter()
+        .zip(ShadingMode::ALL)
+        .find(|(key, _)| rl.is_key_pressed(**key))
+        .map(|(_, mode)| mode)
 }
 
-impl Window {
-    pub fn new(width: i32) -> Self {
-        // aspect ratio 16/9 (width to height)
-        let aspect_ratio: f64 = 16.0 / 9.0;
-
-        let height = (width as f64 / aspect_ratio) as i32;
-        assert!(height > 1);
-        Self { width, height }
-    }
+struct Options {
+    // Options passed on the command line. Without an output path the
+    // scene is shown in the interactive window, otherwise it is rendered
+    // once using adaptive sampling and written to disk.
+    config_path: String,
+    output_path: Option<String>,
+    heat_map_path: Option<String>,
+    sampling: AdaptiveSampling,
+    // Fraction of the window resolution used for rendering in the window.
+    render_scale: f64,
+    bindings_path: Option<String>,
+    // Either "png" or "ppm"
+    screenshot_format: String
 }
 
+fn parse_arguments(args: &[String]) -> Result<Options, ArgumentError> {
+    let mut args = args.iter().skip(1);
+    let config_path = args.next().ok_or(ArgumentError)?.clone();
+    let mut options = Options {
+        config_path,
+        output_path: None,
+        heat_map_path: None,
+        sampling: AdaptiveSampling::default(),
+        render_scale: 1.0,
+        bindings_path: None,
+        screenshot_format: "png".to_string()
+    };
+
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(ArgumentError)?;
+        match flag.as_str() {
+            "--output" => options.output_path = Some(value.clone()),
+            "--heat-map" => options.heat_map_path = Some(value.clone()),
+            "--min-spp" => options.sampling.min_spp = value.parse().map_err(|_| ArgumentError)?,
+            "--max-spp" => options.sampling.max_spp = value.parse().map_err(|_| ArgumentError)?,
+            "--threshold" => options.sampling.threshold = value.parse().map_err(|_| ArgumentError)?,
+            "--scale" => options.render_scale = value.parse().map_err(|_| ArgumentError)?,
+            "--bindings" => options.bindings_path = Some(value.clone()),
+            "--screenshot-format" => options.screenshot_format = value.clone(),
+            _ => return Err(ArgumentError)
+        }
+    }
 
-struct Camera {
-    // The camera is the location from which the rays are shot.
-    // Each ray shot through the viewport originates at the camera
-    // origin.
-    location: Point3
-}
-
-impl Camera {
-    pub fn new(location: Point3) -> Self {
-        Self { location }
+    if options.sampling.min_spp == 0 || options.sampling.min_spp > options.sampling.max_spp {
+        return Err(ArgumentError);
+    }
+    if !(options.sampling.threshold.is_finite() && options.sampling.threshold > 0.0) {
+        return Err(ArgumentError);
+    }
+    if !(options.render_scale > 0.0 && options.render_scale <= 1.0) {
+        return Err(ArgumentError);
     }
+    if !["png", "ppm"].contains(&options.screenshot_format.as_str()) {
+        return Err(ArgumentError);
+    }
+    if options.heat_map_path.is_some() && options.output_path.is_none() {
+        return Err(ArgumentError);
+    }
+    Ok(options)
 }
 
+fn render_to_file(options: &Options, camera: &Camera, intersectables: &Bvh) -> Result<(), Box<dyn Error>> {
+    let window = Window::new(1024);
+    let viewport = Viewport::new(&window, camera);
 
-struct Viewport {
-    // The viewport described the small window through which
-    // the rays are shot into the world. This is a 2D plane in
-    // front of the camera.
-    starting_pixel: Vec3,
-    pixel_delta_u: Vec3,
-    pixel_delta_v: Vec3
-}
+    let mut framebuffer = Framebuffer::new(window.width, window.height);
+    let mut rng = rand::thread_rng();
+    render::render_adaptive(&mut framebuffer, &viewport, camera, intersectables, &options.sampling, &mut rng);
 
-impl Viewport {
-    pub fn new(window: &Window, camera: &Camera) -> Self {
-        // The viewport is a 2D rectangle in front of the camera where
-        // we are shooting our rays through. Its important that we define
-        // its height and width using our choosen aspect ratio.
-        let focal_length = 1.0;     // focal length is the length from origin to the viewport
-        let viewport_height = 2.0;
-        let viewport_width = viewport_height * (window.width as f64 / window.height as f64);
-
-        // We need two vectors across horizontal and down the vertical viewport edges
-        let viewport_u = Vec3::new(viewport_width, 0.0, 0.0);
-        let viewport_v = Vec3::new(0.0, -viewport_height, 0.0);
-
-        // We also need two vectors that define that span the distance between two pixels.
-        let pixel_delta_u = viewport_u / window.width as f64;
-        let pixel_delta_v = viewport_v / window.height as f64;
-
-        // Get a vector to the upper left pixel by using the focal lenght and our viewport vectors
-        // then use our delta pixels to get the exact location of the pixel itself.
-        let viewport_upper_left = camera.location - Vec3::new(0.0, 0.0, focal_length) - (viewport_u/2.0) - (viewport_v / 2.0);
-        let starting_pixel = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
-        Self { starting_pixel, pixel_delta_u, pixel_delta_v }
+    if let Some(output_path) = &options.output_path {
+        ppm::render_ppm_image_ascii(Path::new(output_path), window.height, window.width, 255, &framebuffer.as_rgb_tuples())?;
+    }
+    if let Some(heat_map_path) = &options.heat_map_path {
+        let heat_map = framebuffer.samples_heat_map(options.sampling.min_spp, options.sampling.max_spp);
+        ppm::render_ppm_image_ascii(Path::new(heat_map_path), window.height, window.width, 255, &heat_map)?;
     }
+    Ok(())
 }
 
-fn move_camera_on_key_press(rl: &RaylibHandle, camera: &mut Camera) {
-    let step_size = 0.5;
-
-    if rl.is_key_pressed(KeyboardKey::KEY_W) || rl.is_key_pressed(KeyboardKey::KEY_UP) {
-        println!("Moving forward.");
-        camera.location.z += step_size;
-    }
-    if rl.is_key_pressed(KeyboardKey::KEY_S) || rl.is_key_pressed(KeyboardKey::KEY_DOWN) {
-        println!("Moving backwards.");
-        camera.location.z -= step_size;
-    }
-    if rl.is_key_pressed(KeyboardKey::KEY_A) || rl.is_key_pressed(KeyboardKey::KEY_LEFT){
-        println!("Moving to the left.");
-        camera.location.x += step_size;
-    }
-    if rl.is_key_pressed(KeyboardKey::KEY_D) || rl.is_key_pressed(KeyboardKey::KEY_RIGHT) {
-        println!("Moving to the right.");
-        camera.location.x -= step_size;
-    }
-    if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
-        println!("Moving up.");
-        camera.location.y -= step_size;
-    }
-    if rl.is_key_pressed(KeyboardKey::KEY_LEFT_SHIFT) {
-        println!("Moving down.");
-        camera.location.y += step_size;
+/* Write the image as it is accumulated so far to a file named after the current time. */
+fn save_screenshot(options: &Options, framebuffer: &Framebuffer, screen: &Screen) -> Result<String, Box<dyn Error>> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let path = format!("render-{}.{}", timestamp, options.screenshot_format);
+    if options.screenshot_format == "ppm" {
+        ppm::render_ppm_image_ascii(Path::new(&path), framebuffer.height, framebuffer.width, 255, &framebuffer.as_rgb_tuples())?;
+    } else {
+        screen.export_png(&path)?;
     }
+    Ok(path)
+}
+
+/* Edits made in the viewer are saved next to the scene file instead of over it. The
+saved scene has its includes merged in and its nodes flattened, which would lose the layout of the original.
+A glTF model cannot be written back, its edits are saved as a JSON scene. */
+fn edited_scene_path(config_path: &str) -> PathBuf {
+    let path = Path::new(config_path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("scene");
+    let extension = match SceneFormat::from_path(path) {
+        Ok(_) => path.extension().and_then(|e| e.to_str()).unwrap_or("json"),
+        Err(_) => "json"
+    };
+    path.with_file_name(format!("{}.edited.{}", stem, extension))
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
 
-    // 1sth argument should be a path object
+    // 1sth argument should be a path object, followed by optional flags
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        return Err(Box::new(ArgumentError));
+    if args.get(1).map(String::as_str) == Some("convert") {
+        let [_, _, input, output] = args.as_slice() else {
+            return Err(Box::new(ArgumentError));
+        };
+        return convert_scene_file(Path::new(input), Path::new(output));
+    }
+    let options = parse_arguments(&args)?;
+    let mut config = read_config_from_file(&options.config_path)?;
+    let mut intersectables = Bvh::new(config.intersectables());
+
+    if options.output_path.is_some() {
+        for warning in &config.warnings {
+            eprintln!("warning: {}", warning);
+        }
+        let camera = config.camera.unwrap_or(Camera::new(Point3::new(0.0, 0.0, 0.0)));
+        return render_to_file(&options, &camera, &intersectables);
     }
-    
-    let p = &args[1];
-    let config = read_config_from_file(p)?;
 
     let image_width = 1024;
     let window = Window::new(image_width);
-    let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0));
-    let viewport = Viewport::new(&window, &camera);
+    // The image can be rendered at a lower resolution and upscaled to the window
+    // to keep navigating the scene fluid.
+    let render_window = Window::new((image_width as f64 * options.render_scale) as i32);
+    let mut camera = config.camera.unwrap_or(Camera::new(Point3::new(0.0, 0.0, 0.0)));
+    let mut viewport = Viewport::new(&render_window, &camera);
+
+    let bindings = match &options.bindings_path {
+        Some(path) => KeyBindings::read_from_file(path)?,
+        None => KeyBindings::default()
+    };
+    let mut controller = CameraController::new(bindings);
+    let mut editor = Editor::new();
+    let mut shading = Shading::default();
+    let mut mode_before_edit: Option<ShadingMode> = None;
+    let mut hud = Hud::new();
+    if !config.warnings.is_empty() {
+        hud.set_status(config.warnings.join(", "));
+    }
+
+    // The config file is watched while the window is open. A broken file
+    // keeps the previous scene on screen together with the error.
+    let mut watcher = ConfigWatcher::new(&config, Duration::from_millis(500));
+    let mut reload_error: Option<String> = None;
  
     let (mut rl, thread) = raylib::init()
         .size(window.width, window.height)
         .title("Raytracer")
         .build();
 
-    let mut intersectables: Vec<Box<dyn Intersectable>> = Vec::new();
-    for sphere in config.spheres {
-        intersectables.push(Box::new(sphere));
-    }
-    for plane in config.planes {
-        intersectables.push(Box::new(plane));
-    }
-     
+    // Samples are accumulated over frames into the framebuffer, each
+    // frame adds one sample per pixel and the result is presented
+    // through a single texture covering the whole window.
+    let mut framebuffer = Framebuffer::new(render_window.width, render_window.height);
+    let mut screen = Screen::new(&mut rl, &thread, &window, &render_window)?;
+    let mut rng = rand::thread_rng();
+
     while !rl.window_should_close() {
 
-        // first check if any key was pressed. If so, update the camera position.
-        move_camera_on_key_press(&rl, &mut camera);
+        match watcher.poll() {
+            Some(Ok(reloaded)) => {
+                config = reloaded;
+                intersectables = Bvh::new(config.intersectables());
+                editor.select(None);
+                framebuffer.clear();
+                reload_error = None;
+                if !config.warnings.is_empty() {
+                    hud.set_status(config.warnings.join(", "));
+                }
+            },
+            Some(Err(e)) => reload_error = Some(format!("Failed to reload {}: {}", options.config_path, e)),
+            None => ()
+        }
+
+        // first check the keyboard and mouse. If the camera changed, move the
+        // viewport along and start accumulating from scratch.
+        let selected = editor.selected.map(|object| config.origin_of(object));
+        if controller.update(&rl, &mut camera, selected) {
+            viewport = Viewport::new(&render_window, &camera);
+            framebuffer.clear();
+        }
+
+        // clicking selects the object under the cursor, or deselects when nothing was hit.
+        // Clicks on the property panel do not reach the scene behind it.
+        let mouse = rl.get_mouse_position();
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) && !editor.covers(&config, rl.get_screen_width(), mouse.x, mouse.y) {
+            let x = (mouse.x as f64 * options.render_scale) as i32;
+            let y = (mouse.y as f64 * options.render_scale) as i32;
+            let ray = viewport.get_ray(&camera, x, y, 0.0, 0.0);
+            let picked = render::closest_hit(&ray, &intersectables).and_then(|(i, _)| config.object_at(i));
+            if editor.select(picked) {
+                framebuffer.clear();
+            }
+        }
+
+        if let Some(mode) = shading_mode_on_key_press(&rl) {
+            if mode != shading.mode {
+                mode_before_edit = None;
+                shading.mode = mode;
+                framebuffer.clear();
+            }
+        }
+
+        // any change to the selected object is visible immediately. Colors
+        // cannot be seen in the normals view, so editing one switches to
+        // the albedo view until the object is deselected.
+        if editor.update(&rl, &mut config) {
+            intersectables = Bvh::new(config.intersectables());
+            framebuffer.clear();
+            if editor.editing_color(&config) && shading.mode != ShadingMode::Albedo {
+                mode_before_edit.get_or_insert(shading.mode);
+                shading.mode = ShadingMode::Albedo;
+            }
+        }
+        if editor.selected.is_none() {
+            if let Some(mode) = mode_before_edit.take() {
+                shading.mode = mode;
+                framebuffer.clear();
+            }
+        }
+
+        // shoot a ray through each pixel into the scene and add the color to the accumulated image
+        shading.highlight = editor.selected.map(|o| config.index_of(o));
+        let start = Instant::now();
+        render::render_sample_pass(&mut framebuffer, &viewport, &camera, &intersectables, &shading, &mut rng);
+        hud.record_pass((framebuffer.width * framebuffer.height) as usize, start.elapsed());
+        screen.update(&framebuffer);
+
+        if rl.is_key_pressed(KeyboardKey::KEY_F1) {
+            hud.visible = !hud.visible;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F12) {
+            match save_screenshot(&options, &framebuffer, &screen) {
+                Ok(path) => hud.set_status(format!("Saved {}", path)),
+                Err(e) => hud.set_status(format!("Failed to save screenshot: {}", e))
+            }
+        }
+        let control = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+        if control && rl.is_key_pressed(KeyboardKey::KEY_S) {
+            let path = edited_scene_path(&options.config_path);
+            match config.write_to_file(&path) {
+                Ok(()) if config.flattened => hud.set_status(format!("Saved {}, its nodes are saved as instances", path.display())),
+                Ok(()) => hud.set_status(format!("Saved {}", path.display())),
+                Err(e) => hud.set_status(format!("Failed to save scene: {}", e))
+            }
+        }
 
         let mut d = rl.begin_drawing(&thread);
 
         // clear the display
         d.clear_background(Color::WHITE);
-
-
-        // shoot each ray into the scene and check what the returned color looks like
-        for (y, x) in iproduct!(0..window.height, 0..window.width) {
-            let pixel_center = viewport.starting_pixel + (x as f64 * viewport.pixel_delta_u) + (y as f64 * viewport.pixel_delta_v);
-            let ray_direction = pixel_center - camera.location;
-            let ray = Ray::new(camera.location, ray_direction);
-
-            // TODO find() short-circuits meaning you could have objects
-            // behind one another and render the wrong one.
-            let intersectable = intersectables
-                .iter()
-                .find(|o| o.intersect(&ray));
-            
-            let color = match intersectable {
-                Some(object) => {
-                    let hits = object.intersect_hits(&ray).unwrap();
-                    let h = hits.first().unwrap();
-
-                    // for now a nice color created using the normal
-                    if h.t > 0.0 {
-                        let c = RGBAColor::new(
-                            h.normal.x as f32 + 1.0,
-                            h.normal.y as f32 + 1.0,
-                            h.normal.z as f32 + 1.0
-                        ).unwrap();
-                        c * 0.5 * 255.99
-                    }
-                    else {
-                        RGBAColor::white_blue_blend_over_y(&ray)
-                    }
-                },
-                None => {
-                    RGBAColor::white_blue_blend_over_y(&ray)
-                }
-            };
-            d.draw_pixel(x, y, color);
+        screen.draw(&mut d);
+        hud.draw(&mut d, &framebuffer, &camera, shading.mode);
+        editor.draw(&mut d, &config);
+
+        if let Some(error) = &reload_error {
+            let y = window.height - 40;
+            d.draw_rectangle(0, y, window.width, 40, Color::new(120, 0, 0, 220));
+            d.draw_text(error, 10, y + 12, 16, Color::WHITE);
         }
     }
 
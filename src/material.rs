@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::RGBAColor;
+use crate::gltf_scene;
+use crate::hit::Hit;
+use crate::point::Point3;
+use crate::real::Real;
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Texture {
+    // Most textures are procedural, the color is computed from the point
+    // on the surface in world space. An image is looked up at the
+    // texture coordinates of the hit instead. It is read from a PNG or
+    // JPEG file, or from a glTF file when index picks one of its images.
+    // The path is relative to the scene file.
+    Solid { color: RGBAColor },
+    Checker { even: RGBAColor, odd: RGBAColor, scale: Real },
+    Image {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<usize>,
+        // Filled in when the scene is read
+        #[serde(skip)]
+        image: Arc<Image>
+    }
+}
+
+impl Texture {
+    pub fn color_at(&self, p: &Point3, uv: (Real, Real)) -> RGBAColor {
+        match self {
+            Texture::Solid { color } => *color,
+            Texture::Checker { even, odd, scale } => {
+                let cell = (p.x / scale).floor() + (p.y / scale).floor() + (p.z / scale).floor();
+                if cell.rem_euclid(2.0) == 0.0 { *even } else { *odd }
+            },
+            Texture::Image { image, .. } => image.sample(uv)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Image {
+    // The texels row by row, starting at the top left corner.
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<RGBAColor>
+}
+
+impl Image {
+    /* Look up the texel under a texture coordinate, the image repeats outside 0 to 1. An empty image is white. */
+    pub fn sample(&self, (u, v): (Real, Real)) -> RGBAColor {
+        if self.texels.is_empty() {
+            return RGBAColor { r: 255.0, g: 255.0, b: 255.0, a: 255.0 };
+        }
+        let x = ((u.rem_euclid(1.0) * self.width as Real) as usize).min(self.width - 1);
+        let y = ((v.rem_euclid(1.0) * self.height as Real) as usize).min(self.height - 1);
+        self.texels[y * self.width + x]
+    }
+}
+
+/* Read the image of an image texture. The index picks an image from a glTF file, other files hold a single image. */
+pub fn read_image(path: &Path, index: Option<usize>) -> Result<Image, Box<dyn Error>> {
+    match index {
+        Some(index) => gltf_scene::read_image(path, index),
+        None => {
+            let image = image::open(path)?.to_rgba8();
+            Ok(Image {
+                width: image.width() as usize,
+                height: image.height() as usize,
+                texels: image.pixels().map(|p| RGBAColor { r: p[0] as f32, g: p[1] as f32, b: p[2] as f32, a: p[3] as f32 }).collect()
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Material {
+    // A material is declared once in the scene file and referenced by
+    // name from the objects. It has either a plain color or the name of
+    // a texture. How metallic and rough the surface is, both between 0
+    // and 1, is kept for when the renderer shades with lights.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<RGBAColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metallic: Option<Real>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roughness: Option<Real>
+}
+
+
+#[derive(Debug, Clone)]
+pub enum Surface {
+    // What an object looks like once the names in the scene file are
+    // resolved.
+    Color(RGBAColor),
+    Texture(Texture)
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Surface::Color(RGBAColor { r: 255.0, g: 255.0, b: 255.0, a: 255.0 })
+    }
+}
+
+impl Surface {
+    pub fn color_at(&self, hit: &Hit) -> RGBAColor {
+        match self {
+            Surface::Color(color) => *color,
+            Surface::Texture(texture) => texture.color_at(&hit.p, hit.uv)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checker_alternates() {
+        let even = RGBAColor::new(255.0, 255.0, 255.0).unwrap();
+        let odd = RGBAColor::new(0.0, 0.0, 0.0).unwrap();
+        let checker = Texture::Checker { even, odd, scale: 1.0 };
+
+        assert_eq!(checker.color_at(&Point3::new(0.5, 0.5, 0.5), (0.0, 0.0)).r, 255.0);
+        assert_eq!(checker.color_at(&Point3::new(1.5, 0.5, 0.5), (0.0, 0.0)).r, 0.0);
+        // negative coordinates continue the pattern instead of mirroring it
+        assert_eq!(checker.color_at(&Point3::new(-0.5, 0.5, 0.5), (0.0, 0.0)).r, 0.0);
+    }
+
+    #[test]
+    fn test_images_repeat_and_may_be_empty() {
+        let black = RGBAColor::new(0.0, 0.0, 0.0).unwrap();
+        let white = RGBAColor::new(255.0, 255.0, 255.0).unwrap();
+        let image = Image { width: 2, height: 1, texels: vec![black, white] };
+
+        assert_eq!(image.sample((0.25, 0.5)).r, 0.0);
+        assert_eq!(image.sample((0.75, 0.5)).r, 255.0);
+        assert_eq!(image.sample((1.0, 0.0)).r, 0.0);
+        assert_eq!(image.sample((-0.25, 0.0)).r, 255.0);
+        assert_eq!(Image::default().sample((0.5, 0.5)).r, 255.0);
+    }
+}
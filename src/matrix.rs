@@ -0,0 +1,181 @@
+use std::ops;
+
+use crate::point::Point3;
+use crate::real::Real;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    // A 4x4 matrix in homogeneous coordinates, stored row by row so
+    // m[row][column]. Points are columns (x, y, z, 1) and directions
+    // (x, y, z, 0), which makes the last column the translation.
+    pub m: [[Real; 4]; 4]
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self { m: [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0]
+    ] };
+
+    pub fn new(m: [[Real; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    /* Build a matrix from its columns, the order used by OpenGL and glTF. */
+    pub fn from_columns(columns: [[Real; 4]; 4]) -> Self {
+        Self { m: columns }.transpose()
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = self.m[column][row];
+            }
+        }
+        Self { m }
+    }
+
+    /* The 2x2 determinants of the top two rows and of the bottom two rows. Both the
+    determinant and the inverse are built from them, which saves expanding 3x3 minors. */
+    fn sub_determinants(&self) -> ([Real; 6], [Real; 6]) {
+        let a = &self.m;
+        let s = [
+            a[0][0] * a[1][1] - a[1][0] * a[0][1],
+            a[0][0] * a[1][2] - a[1][0] * a[0][2],
+            a[0][0] * a[1][3] - a[1][0] * a[0][3],
+            a[0][1] * a[1][2] - a[1][1] * a[0][2],
+            a[0][1] * a[1][3] - a[1][1] * a[0][3],
+            a[0][2] * a[1][3] - a[1][2] * a[0][3]
+        ];
+        let c = [
+            a[2][0] * a[3][1] - a[3][0] * a[2][1],
+            a[2][0] * a[3][2] - a[3][0] * a[2][2],
+            a[2][0] * a[3][3] - a[3][0] * a[2][3],
+            a[2][1] * a[3][2] - a[3][1] * a[2][2],
+            a[2][1] * a[3][3] - a[3][1] * a[2][3],
+            a[2][2] * a[3][3] - a[3][2] * a[2][3]
+        ];
+        (s, c)
+    }
+
+    pub fn determinant(&self) -> Real {
+        let (s, c) = self.sub_determinants();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    /* The inverse matrix, or None when the matrix flattens space and cannot be undone. The determinant is
+    compared to the product of the column lengths, which bounds it, so a matrix that only scales everything
+    down a lot is still inverted. */
+    pub fn inverse(&self) -> Option<Self> {
+        let (s, c) = self.sub_determinants();
+        let determinant = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        let scale: Real = (0..4).map(|column| self.m.iter().map(|row| row[column] * row[column]).sum::<Real>().sqrt()).product();
+        if determinant.abs() <= 16.0 * Real::EPSILON * scale {
+            return None;
+        }
+        let a = &self.m;
+        let inverse = [
+            [
+                a[1][1] * c[5] - a[1][2] * c[4] + a[1][3] * c[3],
+                -a[0][1] * c[5] + a[0][2] * c[4] - a[0][3] * c[3],
+                a[3][1] * s[5] - a[3][2] * s[4] + a[3][3] * s[3],
+                -a[2][1] * s[5] + a[2][2] * s[4] - a[2][3] * s[3]
+            ],
+            [
+                -a[1][0] * c[5] + a[1][2] * c[2] - a[1][3] * c[1],
+                a[0][0] * c[5] - a[0][2] * c[2] + a[0][3] * c[1],
+                -a[3][0] * s[5] + a[3][2] * s[2] - a[3][3] * s[1],
+                a[2][0] * s[5] - a[2][2] * s[2] + a[2][3] * s[1]
+            ],
+            [
+                a[1][0] * c[4] - a[1][1] * c[2] + a[1][3] * c[0],
+                -a[0][0] * c[4] + a[0][1] * c[2] - a[0][3] * c[0],
+                a[3][0] * s[4] - a[3][1] * s[2] + a[3][3] * s[0],
+                -a[2][0] * s[4] + a[2][1] * s[2] - a[2][3] * s[0]
+            ],
+            [
+                -a[1][0] * c[3] + a[1][1] * c[1] - a[1][2] * c[0],
+                a[0][0] * c[3] - a[0][1] * c[1] + a[0][2] * c[0],
+                -a[3][0] * s[3] + a[3][1] * s[1] - a[3][2] * s[0],
+                a[2][0] * s[3] - a[2][1] * s[1] + a[2][2] * s[0]
+            ]
+        ];
+        Some(Self { m: inverse.map(|row| row.map(|value| value / determinant)) })
+    }
+
+    /* Multiply a point, which picks up the translation. */
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let row = |r: [Real; 4]| r[0] * p.x + r[1] * p.y + r[2] * p.z + r[3];
+        let w = row(self.m[3]);
+        Point3::new(row(self.m[0]) / w, row(self.m[1]) / w, row(self.m[2]) / w)
+    }
+
+    /* Multiply a direction, which ignores the translation. */
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let row = |r: [Real; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vec3::new(row(self.m[0]), row(self.m[1]), row(self.m[2]))
+    }
+}
+
+// Implement Mat4 * Mat4, the right matrix is applied first
+impl ops::Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * rhs.m[k][column]).sum();
+            }
+        }
+        Self { m }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::real::TOLERANCE;
+
+    #[test]
+    fn test_inverse_undoes_the_matrix() {
+        let m = Mat4::new([
+            [2.0, 0.0, 1.0, 3.0],
+            [0.0, 1.0, 4.0, -1.0],
+            [1.0, 0.0, 3.0, 2.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        assert_eq!(m.determinant(), 5.0);
+        assert_eq!(m.transpose().transpose(), m);
+
+        let product = m * m.inverse().unwrap();
+        for (row, values) in product.m.iter().enumerate() {
+            for (column, value) in values.iter().enumerate() {
+                let expected = if row == column { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < TOLERANCE);
+            }
+        }
+
+        let flat = Mat4::new([[1.0, 2.0, 0.0, 0.0], [2.0, 4.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]);
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn test_tiny_scales_are_inverted() {
+        // A uniform scale of 1e-4 has a determinant of 1e-12 but undoes fine
+        let tiny = Mat4::new([[1e-4, 0.0, 0.0, 5.0], [0.0, 1e-4, 0.0, 0.0], [0.0, 0.0, 1e-4, 0.0], [0.0, 0.0, 0.0, 1.0]]);
+        let inverse = tiny.inverse().unwrap();
+        assert!((inverse.m[0][0] - 1e4).abs() < 1e4 * TOLERANCE);
+        assert!((inverse.m[0][3] + 5e4).abs() < 1e4 * TOLERANCE);
+
+        let flat = Mat4::new([[1e-4, 2e-4, 0.0, 0.0], [2e-4, 4e-4, 0.0, 0.0], [0.0, 0.0, 1e-4, 0.0], [0.0, 0.0, 0.0, 1.0]]);
+        assert!(flat.inverse().is_none());
+    }
+}
@@ -0,0 +1,290 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bounds::{around, hits_box, hits_box_packet};
+use crate::color::RGBAColor;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::normal::Normal3;
+use crate::packet::RayPacket;
+use crate::ply::read_ply;
+use crate::point::Point3;
+use crate::ray::Ray;
+use crate::real::{to_f32, Real};
+use crate::simd::{Lanes, Simd, Vec3x};
+use crate::stl::read_stl;
+use crate::traits::Intersectable;
+
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Mesh {
+    // A triangle mesh. Every triangle lists the indices of its three
+    // corners in the vertices, counter clockwise seen from the front.
+    // Normals, colors and texture coordinates are optional and given per
+    // vertex, they are blended over the triangles. Vertex colors win over
+    // the color or material of the mesh, image textures are looked up at
+    // the texture coordinates. Instead of listing them, the vertices and
+    // triangles can be read from a PLY or STL file at path, relative to
+    // the scene file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vertices: Vec<Point3>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triangles: Vec<[usize; 3]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<Normal3>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<RGBAColor>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<[Real; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<RGBAColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(skip)]
+    pub surface: Surface,
+    // The corners of the box around all vertices, filled in together
+    // with the surface when the scene is built.
+    #[serde(skip)]
+    pub bounds: Option<(Point3, Point3)>
+}
+
+#[derive(Debug)]
+pub struct MeshFileError(pub String);
+
+impl fmt::Display for MeshFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for MeshFileError {}
+
+/* Read the triangles of a PLY or STL file, the format is picked by the extension. */
+pub fn read_mesh_file(path: &Path) -> Result<Mesh, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let mesh = match extension.to_lowercase().as_str() {
+        "ply" => read_ply(&bytes),
+        "stl" => read_stl(&bytes),
+        _ => Err(MeshFileError("use a .ply or .stl file".to_string()))
+    };
+    mesh.map_err(|e| Box::new(MeshFileError(format!("cannot read mesh {}: {}", path.display(), e))) as Box<dyn Error>)
+}
+
+/* Find where a ray crosses a triangle, returns the distance and the barycentric coordinates of b and c (Möller–Trumbore). */
+fn intersect_triangle(ray: &Ray, a: Point3, b: Point3, c: Point3) -> Option<(Real, Real, Real)> {
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = ray.direction.cross(&e2);
+    let determinant = e1.dot(&p);
+    // The ray runs parallel to the triangle
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+
+    let s = ray.origin - a;
+    let u = s.dot(&p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let v = ray.direction.dot(&q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some((e2.dot(&q) * inverse, u, v))
+}
+
+/* The triangle test for every ray of a packet at once. Returns the distances and barycentric coordinates per lane
+together with the mask of the lanes whose ray crosses the triangle. */
+pub fn intersect_triangle_packet<L: Simd>(packet: &RayPacket<L>, a: Point3, b: Point3, c: Point3) -> (L, L, L, u32) {
+    let e1 = Vec3x::splat(b - a);
+    let e2 = Vec3x::splat(c - a);
+    let p = packet.direction.cross(&e2);
+    let determinant = e1.dot(&p);
+    let parallel = (determinant * determinant).lt(L::splat(1e-24));
+    let inverse = L::splat(1.0) / determinant;
+
+    let s = packet.origin - Vec3x::splat(a.to_vec());
+    let u = s.dot(&p) * inverse;
+    let q = s.cross(&e1);
+    let v = packet.direction.dot(&q) * inverse;
+    let (zero, one) = (L::splat(0.0), L::splat(1.0));
+    let outside = u.lt(zero) | one.lt(u) | v.lt(zero) | one.lt(u + v);
+    (e2.dot(&q) * inverse, u, v, !(parallel | outside) & packet.active & L::all())
+}
+
+impl Mesh {
+
+    pub fn bounds(&self) -> Option<(Point3, Point3)> {
+        around(self.vertices.iter().copied())
+    }
+
+    fn corners(&self, triangle: usize) -> [usize; 3] {
+        self.triangles[triangle]
+    }
+
+    fn hit_at(&self, ray: &Ray, triangle: usize, t: Real, u: Real, v: Real) -> Hit {
+        let [a, b, c] = self.corners(triangle).map(|n| self.vertices[n]);
+        let normal = if self.normals.is_empty() {
+            Normal3::from((b - a).cross(&(c - a)).normalise())
+        } else {
+            let [na, nb, nc] = self.corners(triangle).map(|n| self.normals[n]);
+            (na * (1.0 - u - v) + nb * u + nc * v).normalise()
+        };
+        Hit { primitive: triangle, uv: (u, v), ..Hit::facing(ray, t, normal) }
+    }
+}
+
+impl Intersectable for Mesh {
+
+    fn intersect(&self, ray: &Ray) -> bool {
+        match &self.bounds {
+            Some((min, max)) => hits_box(ray, min, max),
+            None => true
+        }
+    }
+
+    fn intersect_hits(&self, ray: &Ray) -> Option<Vec<Hit>> {
+        // Every triangle is tested, there is no structure inside the mesh yet.
+        let hits: Vec<Hit> = self.triangles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, [a, b, c])| {
+                let (t, u, v) = intersect_triangle(ray, self.vertices[*a], self.vertices[*b], self.vertices[*c])?;
+                Some(self.hit_at(ray, i, t, u, v))
+            })
+            .collect();
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    fn bounding_box(&self) -> Option<(Point3, Point3)> {
+        self.bounds.or_else(|| self.bounds())
+    }
+
+    fn intersect_packet(&self, packet: &RayPacket, closest: &mut [Option<Hit>]) -> u32 {
+        let mut live = match &self.bounds {
+            Some((min, max)) => hits_box_packet(packet, min, max),
+            None => packet.active
+        };
+        if live == 0 {
+            return 0;
+        }
+        // Keep the closest triangle per lane, starting from the hits found so far.
+        let mut best = Lanes::from_fn(|i| closest[i].map_or(Real::INFINITY, |c| c.t));
+        let mut found = [(0, 0.0, 0.0); Lanes::WIDTH];
+        live = 0;
+        for (triangle, [a, b, c]) in self.triangles.iter().enumerate() {
+            let (t, u, v, hits) = intersect_triangle_packet(packet, self.vertices[*a], self.vertices[*b], self.vertices[*c]);
+            let closer = hits & Lanes::splat(0.0).lt(t) & t.lt(best);
+            if closer == 0 {
+                continue;
+            }
+            best = Lanes::select(closer, t, best);
+            for i in packet.lanes(closer) {
+                found[i] = (triangle, u.lane(i), v.lane(i));
+            }
+            live |= closer;
+        }
+        for i in packet.lanes(live) {
+            let (triangle, u, v) = found[i];
+            closest[i] = Some(self.hit_at(&packet.ray(i), triangle, best.lane(i), u, v));
+        }
+        live
+    }
+
+    fn color_at(&self, hit: &Hit) -> RGBAColor {
+        if self.colors.is_empty() {
+            // Without texture coordinates of its own an image is spread
+            // over every triangle.
+            let uv = if self.uvs.is_empty() {
+                hit.uv
+            } else {
+                let [a, b, c] = self.corners(hit.primitive).map(|n| self.uvs[n]);
+                let (u, v) = hit.uv;
+                let w = 1.0 - u - v;
+                (w * a[0] + u * b[0] + v * c[0], w * a[1] + u * b[1] + v * c[1])
+            };
+            return self.surface.color_at(&Hit { uv, ..*hit });
+        }
+        let [a, b, c] = self.corners(hit.primitive).map(|n| self.colors[n]);
+        let (u, v) = (to_f32(hit.uv.0), to_f32(hit.uv.1));
+        let w = 1.0 - u - v;
+        RGBAColor {
+            r: w * a.r + u * b.r + v * c.r,
+            g: w * a.g + u * b.g + v * c.g,
+            b: w * a.b + u * b.b + v * c.b,
+            a: w * a.a + u * b.a + v * c.a
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::real::TOLERANCE;
+    use crate::vec::Vec3;
+
+    #[test]
+    fn test_ray_hits_triangle_with_blended_color() {
+        let red = RGBAColor::new(255.0, 0.0, 0.0).unwrap();
+        let blue = RGBAColor::new(0.0, 0.0, 255.0).unwrap();
+        let mut mesh = Mesh {
+            vertices: vec![Point3::new(-1.0, -1.0, -5.0), Point3::new(1.0, -1.0, -5.0), Point3::new(0.0, 1.0, -5.0)],
+            triangles: vec![[0, 1, 2]],
+            colors: vec![red, red, blue],
+            ..Mesh::default()
+        };
+        mesh.bounds = mesh.bounds();
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.intersect(&ray));
+        let hit = mesh.intersect_hits(&ray).unwrap()[0];
+        assert!((hit.t - 5.0).abs() < TOLERANCE);
+        assert!((hit.normal.z - 1.0).abs() < TOLERANCE);
+        // halfway up the triangle the color is halfway between red and blue
+        let color = mesh.color_at(&hit);
+        assert!((color.r - 127.5).abs() < 1e-3 && (color.b - 127.5).abs() < 1e-3);
+
+        let miss = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!mesh.intersect(&miss));
+    }
+
+    #[test]
+    fn test_packet_finds_the_same_triangles() {
+        // Two triangles, one behind the other
+        let mut mesh = Mesh {
+            vertices: vec![
+                Point3::new(-1.0, -1.0, -5.0), Point3::new(1.0, -1.0, -5.0), Point3::new(0.0, 1.0, -5.0),
+                Point3::new(-2.0, -2.0, -8.0), Point3::new(2.0, -2.0, -8.0), Point3::new(0.0, 2.0, -8.0)
+            ],
+            triangles: vec![[3, 4, 5], [0, 1, 2]],
+            ..Mesh::default()
+        };
+        mesh.bounds = mesh.bounds();
+        let rays: Vec<Ray> = [0.0, 0.2, 0.8, 3.0].iter()
+            .map(|x| Ray::new(Point3::new(*x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)))
+            .collect();
+
+        let packet = RayPacket::new(&rays);
+        let mut closest = [None; Lanes::WIDTH];
+        let replaced = mesh.intersect_packet(&packet, &mut closest);
+        for i in packet.lanes(u32::MAX) {
+            let expected = mesh.intersect_hits(&rays[i]).and_then(|hits| hits.into_iter().min_by(|a, b| a.t.total_cmp(&b.t)));
+            assert_eq!(replaced & (1 << i) != 0, expected.is_some());
+            assert_eq!(closest[i].map(|h| (h.t, h.primitive)), expected.map(|h| (h.t, h.primitive)));
+        }
+        assert_eq!(closest[0].unwrap().primitive, 1);
+        assert_eq!(closest[2].unwrap().primitive, 0);
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::config::Config;
+use crate::instance::Placement;
+use crate::light::Light;
+use crate::quaternion::Rotation;
+use crate::real::Real;
+use crate::transform::Frame;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Node {
+    // A named part of an assembly. The node is scaled, rotated and then
+    // moved within its parent, and its children move along with it.
+    // The shapes are placed at the node like instances, the lights and
+    // camera are given relative to it.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<Rotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Real>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shapes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<Light>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<Camera>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Node>
+}
+
+impl Node {
+    pub fn frame(&self) -> Frame {
+        Frame { translate: self.translate, rotate: self.rotate, scale: self.scale }
+    }
+}
+
+/* Replace the nodes of a scene by the instances, lights and camera they hold, placed in the world. The camera of
+the scene itself is kept, otherwise the first camera found in the graph is used. The names of the nodes are gone
+afterwards, a scene saved from the viewer lists the instances instead. */
+pub fn flatten(config: &mut Config) {
+    config.flattened |= !config.nodes.is_empty();
+    for node in std::mem::take(&mut config.nodes) {
+        add_node(config, node, &Frame::default());
+    }
+}
+
+fn add_node(config: &mut Config, node: Node, parent: &Frame) {
+    // Where the node ends up in the world, which is also where its shapes go.
+    let frame = node.frame().within(parent);
+    let transform = frame.transform();
+
+    for shape in node.shapes {
+        config.instances.push(Placement::at(shape, frame));
+    }
+    config.lights.extend(node.lights.into_iter().map(|light| light.moved(&transform)));
+    if let (None, Some(mut camera)) = (config.camera, node.camera) {
+        camera.location = transform.point(&camera.location);
+        if frame.rotate.is_some() {
+            camera.look_along(transform.direction(&camera.forward()));
+        }
+        config.camera = Some(camera);
+    }
+    for child in node.children {
+        add_node(config, child, &frame);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::point::Point3;
+    use crate::real::consts::FRAC_PI_2;
+    use crate::real::TOLERANCE;
+
+    fn node(name: &str, translate: Vec3, rotate: Option<Rotation>) -> Node {
+        Node {
+            name: name.to_string(), translate: Some(translate), rotate, scale: None,
+            shapes: Vec::new(), lights: Vec::new(), camera: None, children: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_children_move_with_their_parents() {
+        // An arm turned a quarter around y at the shoulder, the hand sits at the end of the arm.
+        let mut hand = node("hand", Vec3::new(0.0, 0.0, -2.0), None);
+        hand.shapes.push("ball".to_string());
+        hand.camera = Some(Camera::new(Point3::new(0.0, 0.0, 0.0)));
+        let mut arm = node("arm", Vec3::new(1.0, 0.0, 0.0), Some(Rotation::Euler { yaw: FRAC_PI_2, pitch: 0.0, roll: 0.0 }));
+        arm.scale = Some(2.0);
+        arm.children.push(hand);
+
+        let mut config = Config { nodes: vec![arm], ..Config::default() };
+        flatten(&mut config);
+        assert!(config.nodes.is_empty() && config.flattened);
+
+        let placement = &config.instances[0];
+        assert_eq!(placement.shape, "ball");
+        assert_eq!(placement.scale, Some(2.0));
+        // the hand is 2 units down the arm, which is doubled and turned to point along x
+        let origin = placement.transform().point(&Point3::new(0.0, 0.0, 0.0));
+        assert!((origin - Point3::new(5.0, 0.0, 0.0)).magnitude() < TOLERANCE);
+
+        let camera = config.camera.unwrap();
+        assert!((camera.location - origin).magnitude() < TOLERANCE);
+        assert!((camera.forward() - Vec3::new(1.0, 0.0, 0.0)).magnitude() < TOLERANCE);
+    }
+}
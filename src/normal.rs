@@ -0,0 +1,127 @@
+use std::ops;
+
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+
+use crate::real::Real;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Normal3<T = Real> {
+    // The direction a surface faces. It is kept apart from Vec3 because
+    // normals do not follow a transform like other directions do, they
+    // go through the inverse transpose to stay perpendicular to the
+    // surface. A normal is not always of unit length.
+    pub x: T,
+    pub y: T,
+    pub z: T
+}
+
+impl<T: Float> Normal3<T> {
+
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Self{x, y, z}
+    }
+
+    pub fn magnitude(&self) -> T {
+        self.to_vec().magnitude()
+    }
+
+    pub fn normalise(&self) -> Self {
+        Self::from(self.to_vec().normalise())
+    }
+
+    pub fn dot(&self, v: &Vec3<T>) -> T {
+        self.to_vec().dot(v)
+    }
+
+    pub fn to_vec(self) -> Vec3<T> {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn cast<U: Float>(self) -> Normal3<U> {
+        Normal3::from(self.to_vec().cast())
+    }
+}
+
+impl<T: Float> From<Vec3<T>> for Normal3<T> {
+    fn from(v: Vec3<T>) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+
+// Implement Normal3 * T, used to weigh normals before they are blended
+impl<T: Float> ops::Mul<T> for Normal3<T> {
+    type Output = Normal3<T>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Self::from(self.to_vec() * rhs)
+    }
+}
+
+impl<T: Float> ops::Add<Normal3<T>> for Normal3<T> {
+    type Output = Normal3<T>;
+
+    fn add(self, rhs: Normal3<T>) -> Self::Output {
+        Self::from(self.to_vec() + rhs.to_vec())
+    }
+}
+
+impl<T: Float> ops::Neg for Normal3<T> {
+    type Output = Normal3<T>;
+
+    fn neg(self) -> Self::Output {
+        Self::from(-self.to_vec())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::real::TOLERANCE;
+    use crate::transform::Transform;
+
+    #[test]
+    fn test_normalise() {
+        let n = Normal3::new(0.0, 3.0, 4.0);
+        assert_eq!(n.magnitude(), 5.0);
+
+        let unit = n.normalise();
+        assert!((unit.magnitude() - 1.0).abs() < TOLERANCE);
+        assert!((unit.y - 0.6).abs() < TOLERANCE && (unit.z - 0.8).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn test_negation_flips_the_side() {
+        let n = -Normal3::new(1.0, -2.0, 3.0);
+        assert_eq!((n.x, n.y, n.z), (-1.0, 2.0, -3.0));
+    }
+
+    #[test]
+    fn test_dot_with_vector() {
+        let n = Normal3::new(0.0, 1.0, 0.0);
+        assert_eq!(n.dot(&Vec3::new(5.0, 2.0, -1.0)), 2.0);
+        assert_eq!(n.dot(&Vec3::new(1.0, 0.0, 1.0)), 0.0);
+        assert_eq!((-n).dot(&Vec3::new(0.0, 3.0, 0.0)), -3.0);
+    }
+
+    #[test]
+    fn test_transform_keeps_normals_perpendicular() {
+        // A 45 degree slope stretched to twice its width. Moved like a
+        // direction the normal would lean with the slope, through the
+        // inverse transpose it stays perpendicular to the stretched slope.
+        let stretch = Transform::scale(Vec3::new(2.0, 1.0, 1.0)).unwrap();
+        let slope = stretch.direction(&Vec3::new(1.0, 1.0, 0.0));
+        let normal = stretch.normal(&Normal3::new(-1.0, 1.0, 0.0));
+
+        assert!(normal.dot(&slope).abs() < TOLERANCE);
+        assert!(stretch.direction(&Vec3::new(-1.0, 1.0, 0.0)).dot(&slope).abs() > 1.0);
+        assert!((normal.magnitude() - 1.0).abs() < TOLERANCE);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::point::Point3;
+use crate::ray::Ray;
+use crate::simd::{Lanes, Simd, Vec3x};
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayPacket<L: Simd = Lanes> {
+    // A ray per lane, traced together. This pays off for coherent rays
+    // like those of neighbouring pixels, which mostly hit the same
+    // objects. Lanes that are not set in active hold a copy of the first
+    // ray and are ignored. The origins are kept as vectors from the
+    // world origin.
+    pub origin: Vec3x<L>,
+    pub direction: Vec3x<L>,
+    pub active: u32
+}
+
+impl<L: Simd> RayPacket<L> {
+    /* Pack up to L::WIDTH rays, which must have been made with Ray::new. */
+    pub fn new(rays: &[Ray]) -> Self {
+        assert!(!rays.is_empty() && rays.len() <= L::WIDTH, "a packet holds 1 to {} rays", L::WIDTH);
+        let ray = |i: usize| rays.get(i).unwrap_or(&rays[0]);
+        Self {
+            origin: Vec3x::from_fn(|i| ray(i).origin.to_vec()),
+            direction: Vec3x::from_fn(|i| ray(i).direction),
+            active: (1 << rays.len()) - 1
+        }
+    }
+
+    pub fn ray(&self, i: usize) -> Ray {
+        Ray { origin: Point3::ORIGIN + self.origin.lane(i), direction: self.direction.lane(i) }
+    }
+
+    /* The indices of the lanes set in the mask and the packet */
+    pub fn lanes(&self, mask: u32) -> impl Iterator<Item = usize> {
+        let mask = mask & self.active;
+        (0..L::WIDTH).filter(move |i| mask & (1 << i) != 0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::real::Real;
+    use crate::simd::RealX8;
+    use crate::vec::Vec3;
+
+    #[test]
+    fn test_partial_packets_only_use_their_rays() {
+        let rays: Vec<Ray> = (0..3).map(|i| Ray::new(Point3::new(i as Real, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0))).collect();
+        let packet = RayPacket::<RealX8>::new(&rays);
+        assert_eq!(packet.lanes(u32::MAX).collect::<Vec<usize>>(), vec![0, 1, 2]);
+        assert_eq!(packet.ray(2).origin.x, 2.0);
+    }
+}
@@ -1,47 +1,102 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::color::RGBAColor;
 use crate::hit::Hit;
+use crate::material::Surface;
+use crate::normal::Normal3;
 use crate::point::Point3;
 use crate::ray::Ray;
-use crate::vec::Vec3;
+use crate::real::Real;
 use crate::traits::Intersectable;
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Plane {
-    origin: Point3,
-    orient: Vec3,
-    color: RGBAColor
+    pub origin: Point3,
+    pub orient: Normal3,
+    // Like spheres, either an inline color or a material name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<RGBAColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    // Planes are seen from both sides. A one sided plane is only seen
+    // from the side its normal points to, rays from behind pass it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub one_sided: bool,
+    #[serde(skip)]
+    pub surface: Surface
 }
 
+impl Plane {
+    /* Where the ray crosses the plane, the hit can be behind the ray origin. */
+    fn distance(&self, ray: &Ray) -> Option<Real> {
+        // t = -(Q - P) ⋅ N / d ⋅ N
+        // t = (P - Q) ⋅ N / d ⋅ N
+        let denominator = self.orient.dot(&ray.direction);
+
+        // If the denominator is close to zero the ray runs along the
+        // plane, it never crosses it or lies within it. A positive
+        // denominator means the ray comes from behind the plane.
+        if denominator.abs() < 1e-6 || (self.one_sided && denominator > 0.0) {
+            return None;
+        }
+        Some(self.orient.dot(&(self.origin - ray.origin)) / denominator)
+    }
+}
 
 impl Intersectable for Plane {
 
     fn intersect(&self, ray: &Ray) -> bool {
-        let denominator = ray.direction.dot(&self.orient);
-        denominator > 1e-6
+        self.distance(ray).is_some()
     }
 
     fn intersect_hits(&self, ray: &Ray) -> Option<Vec<Hit>> {
-        // t = -(Q - P) ⋅ N / d ⋅ N
-        // t = (P - Q) ⋅ N / d ⋅ N
-        let denominator = ray.direction.dot(&self.orient);
-        
-        // If the denominator is zero then no solutions
-        // If the denominator is smaller then zero then t will be negative and
-        // the hit will be behind us.
-        // If the hit is only a bit bigger then zero then we might get
-        // an infinite amount of solutions, which we don't want.
-        if denominator > 1e-6 {
-            let qp = self.origin - ray.origin;
-            let numerator = qp.dot(&self.orient);
-            let t = numerator / denominator;
-            // TODO normal here is not aware of side of the plane it hits
-            Some(vec![Hit{t, p: ray.at(t), normal: self.orient}])
-        }
-        else {
-            None
-        }
+        let t = self.distance(ray)?;
+        Some(vec![Hit::facing(ray, t, self.orient)])
+    }
+
+    fn bounding_box(&self) -> Option<(Point3, Point3)> {
+        None
+    }
+
+    fn color_at(&self, hit: &Hit) -> RGBAColor {
+        self.surface.color_at(hit)
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::vec::Vec3;
+
+    fn floor(one_sided: bool) -> Plane {
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
+        Plane {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            orient: Normal3::new(0.0, 1.0, 0.0),
+            color: Some(color),
+            material: None,
+            one_sided,
+            surface: Surface::Color(color)
+        }
+    }
+
+    #[test]
+    fn test_planes_are_seen_from_both_sides() {
+        let from_above = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let from_below = Ray::new(Point3::new(0.0, -2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let above = floor(false).intersect_hits(&from_above).unwrap()[0];
+        assert!(above.t == 2.0 && above.front_face && above.normal.y == 1.0);
+        let below = floor(false).intersect_hits(&from_below).unwrap()[0];
+        assert!(below.t == 2.0 && !below.front_face && below.normal.y == -1.0);
+
+        assert!(floor(true).intersect(&from_above));
+        assert!(!floor(true).intersect(&from_below));
+        let along = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!floor(false).intersect(&along));
+    }
+}
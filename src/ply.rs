@@ -0,0 +1,296 @@
+use crate::color::RGBAColor;
+use crate::mesh::{Mesh, MeshFileError};
+use crate::normal::Normal3;
+use crate::point::Point3;
+use crate::real::Real;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, MeshFileError> {
+        Ok(match name {
+            "char" | "int8" => Scalar::Int8,
+            "uchar" | "uint8" => Scalar::UInt8,
+            "short" | "int16" => Scalar::Int16,
+            "ushort" | "uint16" => Scalar::UInt16,
+            "int" | "int32" => Scalar::Int32,
+            "uint" | "uint32" => Scalar::UInt32,
+            "float" | "float32" => Scalar::Float32,
+            "double" | "float64" => Scalar::Float64,
+            _ => return Err(MeshFileError(format!("unknown property type \"{}\"", name)))
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    // A single value, or a list of values preceded by their count.
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar)
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(name, _) | Property::List(name, _, _) => name
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>
+}
+
+
+struct Body<'a> {
+    // The data after the header, read one value at a time.
+    bytes: &'a [u8],
+    position: usize,
+    encoding: Encoding
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, MeshFileError> {
+        if self.encoding == Encoding::Ascii {
+            return self.read_word(scalar);
+        }
+        let size = scalar.size();
+        let bytes = self.bytes.get(self.position..self.position + size)
+            .ok_or_else(|| MeshFileError("the file ends in the middle of the data".to_string()))?;
+        self.position += size;
+
+        // Bring the bytes in little endian order so one decoder covers both.
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(bytes);
+        if self.encoding == Encoding::BigEndian {
+            b[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::Int8 => b[0] as i8 as f64,
+            Scalar::UInt8 => b[0] as f64,
+            Scalar::Int16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::UInt16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::Int32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::UInt32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::Float64 => f64::from_le_bytes(b)
+        })
+    }
+
+    fn read_word(&mut self, scalar: Scalar) -> Result<f64, MeshFileError> {
+        let rest = &self.bytes[self.position..];
+        let start = rest.iter().position(|b| !b.is_ascii_whitespace())
+            .ok_or_else(|| MeshFileError("the file ends in the middle of the data".to_string()))?;
+        let length = rest[start..].iter().position(|b| b.is_ascii_whitespace()).unwrap_or(rest.len() - start);
+        self.position += start + length;
+
+        let word = String::from_utf8_lossy(&rest[start..start + length]);
+        word.parse::<f64>().map_err(|_| MeshFileError(format!("expected a {:?} value, got \"{}\"", scalar, word)))
+    }
+}
+
+
+/* Split the header into its elements and find where the data starts. */
+fn read_header(bytes: &[u8]) -> Result<(Encoding, Vec<Element>, usize), MeshFileError> {
+    if !bytes.starts_with(b"ply") {
+        return Err(MeshFileError("not a PLY file, it does not start with \"ply\"".to_string()));
+    }
+    let end = bytes.windows(10).position(|w| w == b"end_header")
+        .ok_or_else(|| MeshFileError("the header has no end_header".to_string()))?;
+    let start_of_data = bytes[end..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |n| end + n + 1);
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in String::from_utf8_lossy(&bytes[..end]).lines().skip(1) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", format, _version] => encoding = Some(match *format {
+                "ascii" => Encoding::Ascii,
+                "binary_little_endian" => Encoding::LittleEndian,
+                "binary_big_endian" => Encoding::BigEndian,
+                _ => return Err(MeshFileError(format!("unknown format \"{}\"", format)))
+            }),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| MeshFileError(format!("bad element count \"{}\"", count)))?,
+                properties: Vec::new()
+            }),
+            ["property", "list", count, item, name] => {
+                let property = Property::List(name.to_string(), Scalar::parse(count)?, Scalar::parse(item)?);
+                elements.last_mut().ok_or_else(|| MeshFileError("property before any element".to_string()))?.properties.push(property);
+            },
+            ["property", scalar, name] => {
+                let property = Property::Scalar(name.to_string(), Scalar::parse(scalar)?);
+                elements.last_mut().ok_or_else(|| MeshFileError("property before any element".to_string()))?.properties.push(property);
+            },
+            ["comment", ..] | ["obj_info", ..] | [] => {},
+            _ => return Err(MeshFileError(format!("cannot read header line \"{}\"", line)))
+        }
+    }
+    let encoding = encoding.ok_or_else(|| MeshFileError("the header has no format line".to_string()))?;
+    Ok((encoding, elements, start_of_data))
+}
+
+/* Read a PLY file in any of its three encodings. Faces with more than three corners are split into a fan of triangles, elements other than vertices and faces are skipped. */
+pub fn read_ply(bytes: &[u8]) -> Result<Mesh, MeshFileError> {
+    let (encoding, elements, start) = read_header(bytes)?;
+    let mut body = Body { bytes, position: start, encoding };
+    let mut mesh = Mesh::default();
+
+    for element in &elements {
+        let index = |name: &str| element.properties.iter().position(|p| p.name() == name);
+        let position = [index("x"), index("y"), index("z")];
+        let normal = [index("nx"), index("ny"), index("nz")];
+        let color = [index("red"), index("green"), index("blue")];
+        let alpha = index("alpha");
+        let corners = index("vertex_indices").or(index("vertex_index"));
+        if element.name == "vertex" && position.contains(&None) {
+            return Err(MeshFileError("the vertices have no x, y and z".to_string()));
+        }
+
+        for _ in 0..element.count {
+            // Scalars are stored by their position in the element, the
+            // corners of a face are the only list that is kept.
+            let mut values = vec![0.0; element.properties.len()];
+            let mut list = Vec::new();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property {
+                    Property::Scalar(_, scalar) => values[i] = body.read(*scalar)?,
+                    Property::List(_, count, item) => {
+                        let count = body.read(*count)? as usize;
+                        let items = (0..count).map(|_| body.read(*item)).collect::<Result<Vec<f64>, _>>()?;
+                        if Some(i) == corners {
+                            list = items;
+                        }
+                    }
+                }
+            }
+            let get = |i: Option<usize>| i.map(|i| values[i]);
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let [x, y, z] = position.map(|i| get(i).unwrap_or(0.0) as Real);
+                    mesh.vertices.push(Point3::new(x, y, z));
+                    if let [Some(x), Some(y), Some(z)] = normal.map(get) {
+                        mesh.normals.push(Normal3::new(x as Real, y as Real, z as Real));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = color.map(get) {
+                        // Colors are either bytes or floats from 0 to 1.
+                        let scale = match &element.properties[color[0].unwrap()] {
+                            Property::Scalar(_, Scalar::Float32 | Scalar::Float64) => 255.0,
+                            _ => 1.0
+                        };
+                        let a = get(alpha).map_or(255.0, |a| a * scale);
+                        mesh.colors.push(RGBAColor { r: (r * scale) as f32, g: (g * scale) as f32, b: (b * scale) as f32, a: a as f32 });
+                    }
+                },
+                "face" => {
+                    let list: Vec<usize> = list.into_iter().map(|i| i as usize).collect();
+                    for n in 1..list.len().saturating_sub(1) {
+                        mesh.triangles.push([list[0], list[n], list[n + 1]]);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(corner) = mesh.triangles.iter().flatten().find(|&&i| i >= mesh.vertices.len()) {
+        return Err(MeshFileError(format!("a face uses vertex {}, the file has {} vertices", corner, mesh.vertices.len())));
+    }
+    Ok(mesh)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_and_binary_files_agree() {
+        let ascii = b"ply
+format ascii 1.0
+comment a red and blue square
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 255 0 0
+1 1 0 0 0 255
+0 1 0 0 0 255
+4 0 1 2 3
+";
+        let mesh = read_ply(ascii).unwrap();
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.vertices[2].y, 1.0);
+        assert_eq!(mesh.colors[3].b, 255.0);
+
+        // The same square as big endian binary data.
+        let mut binary = b"ply
+format binary_big_endian 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+".to_vec();
+        for (x, y, red) in [(0.0f32, 0.0f32, 255u8), (1.0, 0.0, 255), (1.0, 1.0, 0), (0.0, 1.0, 0)] {
+            for value in [x, y, 0.0] {
+                binary.extend(value.to_be_bytes());
+            }
+            binary.extend([red, 0, 255 - red]);
+        }
+        binary.push(4);
+        for corner in [0i32, 1, 2, 3] {
+            binary.extend(corner.to_be_bytes());
+        }
+        let from_binary = read_ply(&binary).unwrap();
+        assert_eq!(from_binary.triangles, mesh.triangles);
+        assert_eq!(from_binary.vertices[2].y, 1.0);
+        assert_eq!(from_binary.colors[3].b, 255.0);
+
+        assert!(read_ply(&binary[..binary.len() - 2]).is_err());
+    }
+}
@@ -1,37 +1,90 @@
 use std::ops;
 
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+
+use crate::real::Real;
 use crate::vec::Vec3;
 
-pub type Point3 = Vec3;
-
-
-// #[derive(Debug, Deserialize, Clone, Copy)]
-// pub struct Point3 {
-//     pub x: f64,
-//     pub y: f64,
-//     pub z: f64
-// }
-
-// impl ops::Add<Vec3> for Point3 {
-//     type Output = Point3;
-
-//     fn add(self, rhs: Vec3) -> Self::Output {
-//         Self {
-//             x: self.x + rhs.x,
-//             y: self.y + rhs.y,
-//             z: self.z + rhs.z
-//         }
-//     }
-// }
-
-// impl ops::Sub<Point3> for Point3 {
-//     type Output = Point3;
-
-//     fn sub(self, rhs: Point3) -> Self::Output {
-//         Self {
-//             x: self.x - rhs.x,
-//             y: self.y - rhs.y,
-//             z: self.z - rhs.z
-//         }
-//     }
-// }
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Point3<T = Real> {
+    // A position in space. Unlike a vector it has no length or
+    // direction, the difference of two points is the vector between
+    // them and a point moved by a vector is another point.
+    pub x: T,
+    pub y: T,
+    pub z: T
+}
+
+impl Point3 {
+    pub const ORIGIN: Self = Self { x: 0.0, y: 0.0, z: 0.0 };
+}
+
+impl<T: Float> Point3<T> {
+
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Self{x, y, z}
+    }
+
+    /* The vector from the origin to the point */
+    pub fn to_vec(self) -> Vec3<T> {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn distance(&self, p: &Self) -> T {
+        (*self - *p).magnitude()
+    }
+}
+
+
+// Implement Point3 + Vec3, moving the point
+impl<T: Float> ops::Add<Vec3<T>> for Point3<T> {
+    type Output = Point3<T>;
+
+    fn add(self, rhs: Vec3<T>) -> Self::Output {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z
+        }
+    }
+}
+
+// Implement Point3 - Vec3, moving the point back
+impl<T: Float> ops::Sub<Vec3<T>> for Point3<T> {
+    type Output = Point3<T>;
+
+    fn sub(self, rhs: Vec3<T>) -> Self::Output {
+        self + -rhs
+    }
+}
+
+// Implement Point3 - Point3, the vector from rhs to self
+impl<T: Float> ops::Sub<Point3<T>> for Point3<T> {
+    type Output = Vec3<T>;
+
+    fn sub(self, rhs: Point3<T>) -> Self::Output {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_points_and_vectors() {
+        let a = Point3::new(1.0, 2.0, 3.0);
+        let b = Point3::new(4.0, 6.0, 3.0);
+        let v = b - a;
+
+        assert!(v.x == 3.0 && v.y == 4.0 && v.z == 0.0);
+        assert!(a.distance(&b) == 5.0);
+        let moved = a + v;
+        assert!(moved.distance(&b) == 0.0 && (moved - v).distance(&a) == 0.0);
+    }
+}
@@ -9,7 +9,7 @@ P1, P2, P3
 
 */
 
-use std::{fs::File, io::{Error, Write}, path::Path};
+use std::{fs::File, io::{BufWriter, Error, Write}, path::Path};
 
 pub fn render_ppm_image_ascii(
     output_file_path: &Path,
@@ -18,19 +18,16 @@ pub fn render_ppm_image_ascii(
     max_color_value: u8,
     pixels: &Vec<(u8, u8, u8)>
 ) -> Result<(), Error> {
-    let mut image_file = File::create(output_file_path)?;
-    let resolution_header = width.to_string() + &" " + &height.to_string() + &"\n";
-    let max_color_header = max_color_value.to_string() + &"\n";
+    let mut image_file = BufWriter::new(File::create(output_file_path)?);
 
-    image_file.write_all(b"P3\n");
-    image_file.write_all(resolution_header.as_bytes());
-    image_file.write_all(max_color_header.as_bytes());
+    writeln!(image_file, "P3")?;
+    writeln!(image_file, "{} {}", width, height)?;
+    writeln!(image_file, "{}", max_color_value)?;
     for &(r, g, b) in pixels {
-        let line = r.to_string() + &" " + &g.to_string() + &" " + &b.to_string() + &"\n";
-        image_file.write_all(line.as_bytes());
+        writeln!(image_file, "{} {} {}", r, g, b)?;
     }
 
-    Ok(())
+    image_file.flush()
 }
 
 #[cfg(test)]
@@ -53,6 +50,6 @@ mod tests {
             })
             .collect();
 
-        render_ppm_image_ascii(path, h, w, 255, &pixels);
+        render_ppm_image_ascii(path, h, w, 255, &pixels).unwrap();
     }
 }
@@ -0,0 +1,140 @@
+use crate::real::Real;
+use crate::simd::Simd;
+
+
+pub struct Quadratic {
+    // The solutions of a t² + b t + c = 0, the smallest first. The
+    // roots are None when the discriminant is negative, a touching ray
+    // gives the same root twice.
+    pub discriminant: Real,
+    pub roots: Option<[Real; 2]>
+}
+
+impl Quadratic {
+    /* Solve without catastrophic cancellation. The textbook formula subtracts two nearly equal numbers for the
+    root closest to zero when b² is much larger than 4ac, so only the other root is taken from it and the small
+    one follows from the product of the roots being c / a. */
+    pub fn new(a: Real, b: Real, c: Real) -> Self {
+        if a == 0.0 {
+            // Not a quadratic after all, a line crosses zero once.
+            let roots = if b == 0.0 { None } else { Some([-c / b, -c / b]) };
+            return Self { discriminant: b * b, roots };
+        }
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return Self { discriminant, roots: None };
+        }
+        let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+        if q == 0.0 {
+            // b and c are both zero
+            return Self { discriminant, roots: Some([0.0, 0.0]) };
+        }
+        let (t0, t1) = (q / a, c / q);
+        Self { discriminant, roots: Some([t0.min(t1), t0.max(t1)]) }
+    }
+}
+
+/* The stable solution for every lane of t² + 2 half_b t + c = 0, as the near and far root together with the
+mask of the lanes that have roots. Rays of unit length give these for all quadrics. */
+pub fn solve_lanes<L: Simd>(half_b: L, c: L) -> (L, L, u32) {
+    let zero = L::splat(0.0);
+    let discriminant = half_b * half_b - c;
+    let root = discriminant.max(zero).sqrt();
+    let q = zero - L::select(half_b.lt(zero), half_b - root, half_b + root);
+    // q is zero when half_b and c both are, both roots are zero then
+    let flat = !(q.lt(zero) | zero.lt(q)) & L::all();
+    let (t0, t1) = (q, L::select(flat, zero, c / q));
+    (t0.min(t1), t0.max(t1), !discriminant.lt(zero) & L::all())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    use crate::simd::RealX4;
+
+    /* Find the roots by bisection instead, on both sides of the vertex where the polynomial is monotonic. */
+    fn bisect_roots(a: Real, b: Real, c: Real) -> Vec<Real> {
+        let f = |t: Real| (a * t + b) * t + c;
+        let vertex = -b / (2.0 * a);
+        let bound = 1.0 + (b / a).abs().max((c / a).abs());
+        [(-bound, vertex), (vertex, bound)].into_iter()
+            .filter(|(low, high)| f(*low).signum() != f(*high).signum())
+            .map(|(mut low, mut high)| {
+                let rising = f(high) > f(low);
+                loop {
+                    let middle = 0.5 * (low + high);
+                    if middle <= low || middle >= high {
+                        break middle;
+                    }
+                    if (f(middle) > 0.0) == rising { high = middle } else { low = middle }
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_degenerate_equations() {
+        assert!(Quadratic::new(1.0, 0.0, 1.0).roots.is_none());
+        assert_eq!(Quadratic::new(1.0, -2.0, 1.0).roots, Some([1.0, 1.0]));
+        assert_eq!(Quadratic::new(0.0, 2.0, -4.0).roots, Some([2.0, 2.0]));
+        assert!(Quadratic::new(0.0, 0.0, 1.0).roots.is_none());
+    }
+
+    #[test]
+    fn test_lanes_without_b_and_c_have_zero_roots() {
+        let (near, far, hits) = solve_lanes(RealX4::from_fn(|i| i as Real), RealX4::splat(0.0));
+        assert_eq!(hits, 0b1111);
+        assert_eq!((near.lane(0), far.lane(0)), (0.0, 0.0));
+        assert_eq!((near.lane(1), far.lane(1)), (-2.0, 0.0));
+    }
+
+    proptest! {
+        #[test]
+        fn test_roots_match_bisection(
+            a in prop_oneof![-5.0..-0.5 as Real, 0.5..5.0 as Real],
+            r0 in -10.0..10.0 as Real,
+            gap in 0.5..10.0 as Real
+        ) {
+            let (b, c) = (-a * (2.0 * r0 + gap), a * r0 * (r0 + gap));
+            let expected = bisect_roots(a, b, c);
+            prop_assert_eq!(expected.len(), 2);
+
+            let tolerance = 10.0 * Real::EPSILON.sqrt();
+            let roots = Quadratic::new(a, b, c).roots.unwrap();
+            for (root, expected) in roots.iter().zip(&expected) {
+                prop_assert!((root - expected).abs() <= tolerance * expected.abs().max(1.0), "{:?} != {:?}", roots, expected);
+            }
+        }
+
+        #[test]
+        fn test_small_roots_keep_their_precision(
+            small in prop_oneof![-1e-3..-1e-6 as Real, 1e-6..1e-3 as Real],
+            large in 1e3..1e6 as Real
+        ) {
+            // b² is far larger than 4ac, where the textbook formula loses the small root
+            let (b, c) = (-(small + large), small * large);
+            let expected = bisect_roots(1.0, b, c);
+            let roots = Quadratic::new(1.0, b, c).roots.unwrap();
+            let smallest = |roots: &[Real]| roots.iter().copied().min_by(|x, y| x.abs().total_cmp(&y.abs())).unwrap();
+            let (found, small_root) = (smallest(&roots), smallest(&expected));
+            prop_assert!((found - small_root).abs() <= 1e3 * Real::EPSILON * small_root.abs(), "{} != {}", found, small_root);
+        }
+
+        #[test]
+        fn test_lanes_match_scalar(half_b in -10.0..10.0 as Real, c in -10.0..10.0 as Real) {
+            let (near, far, hits) = solve_lanes(RealX4::splat(half_b), RealX4::splat(c));
+            match Quadratic::new(1.0, 2.0 * half_b, c).roots {
+                Some([t0, t1]) => {
+                    prop_assert_eq!(hits, 0b1111);
+                    let tolerance = 10.0 * Real::EPSILON * t0.abs().max(t1.abs()).max(1.0);
+                    prop_assert!((near.lane(0) - t0).abs() <= tolerance && (far.lane(0) - t1).abs() <= tolerance);
+                },
+                None => prop_assert_eq!(hits, 0)
+            }
+        }
+    }
+}
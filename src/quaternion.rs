@@ -0,0 +1,220 @@
+use std::ops;
+
+use serde::{Deserialize, Serialize};
+
+use crate::matrix::Mat4;
+use crate::real::Real;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    // A rotation stored as w + xi + yj + zk. Only unit quaternions are
+    // rotations, the constructors return those.
+    pub w: Real,
+    pub x: Real,
+    pub y: Real,
+    pub z: Real
+}
+
+impl Quaternion {
+    /* Rotate counter clockwise around an axis, seen from the tip of the axis. The angle is in radians. */
+    pub fn from_axis_angle(axis: Vec3, angle: Real) -> Self {
+        let axis = axis.normalise();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Self { w: cos, x: axis.x * sin, y: axis.y * sin, z: axis.z * sin }
+    }
+
+    /* Angles in radians like the camera uses them: yaw turns to the right around the y axis, pitch tilts up around
+    the x axis and roll turns counter clockwise around the z axis. The roll is applied first and the yaw last. */
+    pub fn from_euler(yaw: Real, pitch: Real, roll: Real) -> Self {
+        Self::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), -yaw)
+            * Self::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), pitch)
+            * Self::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), roll)
+    }
+
+    /* The rotation in the upper left of a matrix, which should not be scaled or skewed. */
+    pub fn from_mat4(matrix: &Mat4) -> Self {
+        let m = &matrix.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        // Divide by the largest of the four components to stay accurate.
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self { w: s / 4.0, x: (m[2][1] - m[1][2]) / s, y: (m[0][2] - m[2][0]) / s, z: (m[1][0] - m[0][1]) / s }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Self { w: (m[2][1] - m[1][2]) / s, x: s / 4.0, y: (m[0][1] + m[1][0]) / s, z: (m[0][2] + m[2][0]) / s }
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Self { w: (m[0][2] - m[2][0]) / s, x: (m[0][1] + m[1][0]) / s, y: s / 4.0, z: (m[1][2] + m[2][1]) / s }
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Self { w: (m[1][0] - m[0][1]) / s, x: (m[0][2] + m[2][0]) / s, y: (m[1][2] + m[2][1]) / s, z: s / 4.0 }
+        };
+        q.normalise()
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        let Self { w, x, y, z } = self;
+        Mat4::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ])
+    }
+
+    pub fn dot(&self, q: &Self) -> Real {
+        self.w * q.w + self.x * q.x + self.y * q.y + self.z * q.z
+    }
+
+    pub fn normalise(&self) -> Self {
+        let magnitude = self.dot(self).sqrt();
+        Self { w: self.w / magnitude, x: self.x / magnitude, y: self.y / magnitude, z: self.z / magnitude }
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * u.cross(v);
+        *v + self.w * t + u.cross(&t)
+    }
+
+    /* Turn from self towards q, t = 0 gives self and t = 1 gives q. The rotation speed is constant
+    and the shortest way around is taken. */
+    pub fn slerp(&self, q: &Self, t: Real) -> Self {
+        let a = self.normalise();
+        let mut b = q.normalise();
+        let mut cos = a.dot(&b);
+        // q and -q are the same rotation, the one closer to a is the short way
+        if cos < 0.0 {
+            b = b * -1.0;
+            cos = -cos;
+        }
+        // Almost the same rotation, the angle is too small to divide by
+        if cos > 0.9995 {
+            return (a * (1.0 - t) + b * t).normalise();
+        }
+        let angle = cos.acos();
+        let (sin_total, sin_start, sin_end) = (angle.sin(), ((1.0 - t) * angle).sin(), (t * angle).sin());
+        (a * (sin_start / sin_total) + b * (sin_end / sin_total)).normalise()
+    }
+}
+
+// Implement Quaternion * Quaternion, the right rotation is applied first
+impl ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Self::Output {
+        Self {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w
+        }
+    }
+}
+
+// Implement Quaternion * Real
+impl ops::Mul<Real> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Real) -> Self::Output {
+        Self { w: self.w * rhs, x: self.x * rhs, y: self.y * rhs, z: self.z * rhs }
+    }
+}
+
+impl ops::Add<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, rhs: Quaternion) -> Self::Output {
+        Self { w: self.w + rhs.w, x: self.x + rhs.x, y: self.y + rhs.y, z: self.z + rhs.z }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Rotation {
+    // How a rotation is written in a scene file, either as an angle
+    // around an axis, as a unit quaternion or as the yaw, pitch and roll
+    // of the camera. The angles are in radians like the camera angles.
+    AxisAngle { axis: Vec3, angle: Real },
+    Quaternion { w: Real, x: Real, y: Real, z: Real },
+    Euler {
+        #[serde(default)]
+        yaw: Real,
+        #[serde(default)]
+        pitch: Real,
+        #[serde(default)]
+        roll: Real
+    }
+}
+
+impl Rotation {
+    /* A zero axis or quaternion has no direction to turn around. */
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            Rotation::AxisAngle { axis, .. } if axis.magnitude() == 0.0 => Err("the rotation axis cannot be zero".to_string()),
+            Rotation::Quaternion { w, x, y, z } if w == 0.0 && x == 0.0 && y == 0.0 && z == 0.0 => {
+                Err("the rotation quaternion cannot be zero".to_string())
+            },
+            _ => Ok(())
+        }
+    }
+
+    /* Turn by self after turning by first. */
+    pub fn after(&self, first: &Rotation) -> Self {
+        let Quaternion { w, x, y, z } = self.quaternion() * first.quaternion();
+        Rotation::Quaternion { w, x, y, z }
+    }
+
+    pub fn quaternion(&self) -> Quaternion {
+        match *self {
+            Rotation::AxisAngle { axis, angle } => Quaternion::from_axis_angle(axis, angle),
+            Rotation::Quaternion { w, x, y, z } => Quaternion { w, x, y, z }.normalise(),
+            Rotation::Euler { yaw, pitch, roll } => Quaternion::from_euler(yaw, pitch, roll)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::real::consts::{FRAC_PI_2, PI};
+    use crate::real::TOLERANCE;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < TOLERANCE, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_rotations_agree_with_matrices() {
+        let q = Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), FRAC_PI_2);
+        assert_close(q.rotate(&Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+
+        // the camera convention, yaw turns right and pitch looks up
+        let camera = Quaternion::from_euler(FRAC_PI_2, 0.3, 0.0);
+        let forward = Vec3::new(0.0, 0.0, -1.0);
+        assert_close(camera.rotate(&forward), Vec3::new((0.3 as Real).cos(), (0.3 as Real).sin(), 0.0));
+        assert_close(camera.to_mat4().transform_vector(&forward), camera.rotate(&forward));
+
+        let back = Quaternion::from_mat4(&camera.to_mat4());
+        assert!((back.dot(&camera).abs() - 1.0).abs() < TOLERANCE);
+        let turned = Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), PI);
+        assert!((Quaternion::from_mat4(&turned.to_mat4()).dot(&turned).abs() - 1.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn test_slerp_turns_at_constant_speed() {
+        let start = Quaternion { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+        let end = Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), FRAC_PI_2);
+        let quarter = start.slerp(&end, 0.25);
+        assert_close(quarter.rotate(&Vec3::new(1.0, 0.0, 0.0)), Vec3::new((PI / 8.0).cos(), 0.0, -(PI / 8.0).sin()));
+        // -end is the same rotation and must not take the long way around
+        let negated = start.slerp(&(end * -1.0), 0.25);
+        assert!((negated.dot(&quarter).abs() - 1.0).abs() < TOLERANCE);
+        assert!((start.slerp(&end, 1.0).dot(&end) - 1.0).abs() < TOLERANCE);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::point::Point3;
+use crate::real::Real;
 use crate::vec::Vec3;
 
 
@@ -17,7 +18,7 @@ impl Ray {
         }
     }
 
-    pub fn at(&self, t: f64) -> Point3 {
+    pub fn at(&self, t: Real) -> Point3 {
         self.origin + t * self.direction
     }
 }
@@ -0,0 +1,27 @@
+// The floating point type of the geometry. Rays, objects, matrices and
+// the rest of the math use it, colors stay f32 either way. The default
+// is f64, building with the `f32` feature trades precision for speed.
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+#[cfg(feature = "f32")]
+pub type Real = f32;
+
+// Pi and friends in the precision of Real.
+#[cfg(not(feature = "f32"))]
+#[allow(unused_imports)]
+pub use std::f64::consts;
+#[cfg(feature = "f32")]
+#[allow(unused_imports)]
+pub use std::f32::consts;
+
+/* Narrow to the f32 the colors use. In an f32 build there is nothing to do. */
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f32(value: Real) -> f32 {
+    value as f32
+}
+
+// How close results have to be in the tests, f32 only keeps about seven digits.
+#[cfg(all(test, not(feature = "f32")))]
+pub const TOLERANCE: Real = 1e-9;
+#[cfg(all(test, feature = "f32"))]
+pub const TOLERANCE: Real = 1e-4;
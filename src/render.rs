@@ -0,0 +1,325 @@
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::bvh::Bvh;
+use crate::camera::{Camera, Viewport};
+use crate::color::RGBAColor;
+use crate::framebuffer::Framebuffer;
+use crate::hit::Hit;
+use crate::packet::RayPacket;
+use crate::ray::Ray;
+use crate::real::{to_f32, Real};
+use crate::simd::{Lanes, Simd};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadingMode {
+    // The colors derived from the surface normal.
+    Normals,
+    // Grey scale of the distance to the hit, white is close by.
+    Depth,
+    // White where an object was hit, black where the ray escaped.
+    HitMask,
+    // Heat map of the intersection tests performed for the ray, the
+    // boxes of the BVH and the quick and exact tests of the objects in
+    // the boxes it passes through.
+    IntersectionTests,
+    // Heat map of the time it took to trace the ray.
+    RenderTime,
+    // The plain color of the object that was hit.
+    Albedo
+}
+
+impl ShadingMode {
+    pub const ALL: [ShadingMode; 6] = [
+        ShadingMode::Normals,
+        ShadingMode::Depth,
+        ShadingMode::HitMask,
+        ShadingMode::IntersectionTests,
+        ShadingMode::RenderTime,
+        ShadingMode::Albedo
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShadingMode::Normals => "normals",
+            ShadingMode::Depth => "depth",
+            ShadingMode::HitMask => "hit mask",
+            ShadingMode::IntersectionTests => "intersection tests",
+            ShadingMode::RenderTime => "render time",
+            ShadingMode::Albedo => "albedo"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Shading {
+    // How the color of a ray is determined. The object at index
+    // highlight, if any, is tinted yellow.
+    pub mode: ShadingMode,
+    pub highlight: Option<usize>
+}
+
+impl Default for Shading {
+    fn default() -> Self {
+        Self { mode: ShadingMode::Normals, highlight: None }
+    }
+}
+
+// Hits further away than this are black in the depth view.
+const MAX_DEPTH: Real = 20.0;
+// Rays taking this long or longer are red in the render time view.
+const MAX_RENDER_TIME: Duration = Duration::from_micros(50);
+
+pub struct Trace {
+    // The closest hit along a ray and the index of the object that was
+    // hit, together with the amount of intersection tests that were
+    // needed to find it.
+    pub hit: Option<(usize, Hit)>,
+    pub tests: u32
+}
+
+/* Shoot a ray into the scene and find the closest hit in front of the ray origin over all objects. */
+pub fn trace(ray: &Ray, intersectables: &Bvh) -> Trace {
+    // Only the objects in the boxes the ray passes through are tested.
+    // Objects passing the quick test are tested again for the exact hits.
+    let mut hit: Option<(usize, Hit)> = None;
+    let mut tests = 0;
+    let boxes = intersectables.visit(ray, |i| {
+        let o = &intersectables[i];
+        tests += 1;
+        if !o.intersect(ray) {
+            return;
+        }
+        tests += 1;
+        if let Some(h) = o.nearest_hit(ray).filter(|h| hit.is_none_or(|(_, closest)| h.t < closest.t)) {
+            hit = Some((i, h));
+        }
+    });
+    Trace { hit, tests: boxes + tests }
+}
+
+/* Find the closest hit in front of the ray origin over all objects in the scene,
+together with the index of the object that was hit. */
+pub fn closest_hit(ray: &Ray, intersectables: &Bvh) -> Option<(usize, Hit)> {
+    trace(ray, intersectables).hit
+}
+
+/* Trace a packet of rays together, one trace per lane. The lanes after the rays of the packet have no hit. Each
+box and object is tested once for the lanes of the packet that reach it, the cost of the whole packet is also the
+cost every ray is given. */
+pub fn trace_packet(packet: &RayPacket, intersectables: &Bvh) -> [Trace; Lanes::WIDTH] {
+    let mut closest = [None; Lanes::WIDTH];
+    let mut objects = [0; Lanes::WIDTH];
+    let mut tests = 0;
+    let boxes = intersectables.visit_packet(packet, |i, lanes| {
+        tests += 1;
+        let replaced = intersectables[i].intersect_packet(&RayPacket { active: lanes, ..*packet }, &mut closest);
+        for lane in packet.lanes(replaced) {
+            objects[lane] = i;
+        }
+    });
+    std::array::from_fn(|lane| Trace { hit: closest[lane].map(|h| (objects[lane], h)), tests: boxes + tests })
+}
+
+/* The color seen along a ray. */
+pub fn ray_color(ray: &Ray, intersectables: &Bvh, shading: &Shading) -> RGBAColor {
+    let start = Instant::now();
+    let trace = trace(ray, intersectables);
+    shade(ray, &trace, start.elapsed(), intersectables, shading)
+}
+
+/* The color for a ray that has been traced, elapsed being how long that took. */
+fn shade(ray: &Ray, trace: &Trace, elapsed: Duration, intersectables: &Bvh, shading: &Shading) -> RGBAColor {
+    let c = match (shading.mode, trace.hit) {
+        (ShadingMode::Normals, Some((_, h))) => {
+            // for now a nice color created using the outward normal, so the
+            // back of a surface shows which way it faces
+            let outward = if h.front_face { h.normal } else { -h.normal };
+            let n = outward.cast::<f32>();
+            RGBAColor::new(n.x + 1.0, n.y + 1.0, n.z + 1.0).unwrap() * 0.5 * 255.99
+        },
+        (ShadingMode::Normals, None) => RGBAColor::white_blue_blend_over_y(ray),
+        (ShadingMode::Depth, Some((_, h))) => {
+            let a = to_f32((1.0 - h.t / MAX_DEPTH).max(0.0));
+            RGBAColor::new(255.0, 255.0, 255.0).unwrap() * a
+        },
+        (ShadingMode::Depth | ShadingMode::HitMask, None) => RGBAColor::new(0.0, 0.0, 0.0).unwrap(),
+        (ShadingMode::HitMask, Some(_)) => RGBAColor::new(255.0, 255.0, 255.0).unwrap(),
+        (ShadingMode::IntersectionTests, _) => {
+            // every box and two tests per object is the most a ray can cost
+            let max_tests = (intersectables.node_count() + 2 * intersectables.len()).max(1);
+            RGBAColor::heat(trace.tests as f32 / max_tests as f32)
+        },
+        (ShadingMode::RenderTime, _) => RGBAColor::heat(elapsed.as_secs_f32() / MAX_RENDER_TIME.as_secs_f32()),
+        (ShadingMode::Albedo, Some((i, hit))) => intersectables[i].color_at(&hit),
+        (ShadingMode::Albedo, None) => RGBAColor::white_blue_blend_over_y(ray)
+    };
+
+    match trace.hit {
+        Some((i, _)) if shading.highlight == Some(i) => {
+            let yellow = RGBAColor::new(255.0, 255.0, 0.0).unwrap();
+            (0.5 * c + 0.5 * yellow).unwrap()
+        },
+        _ => c
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveSampling {
+    // Every pixel receives at least min_spp samples. After that samples
+    // are only added to pixels whose estimated error (see
+    // PixelStats::standard_error) is still above the threshold, until
+    // max_spp samples have been taken.
+    pub min_spp: u32,
+    pub max_spp: u32,
+    pub threshold: f32
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self { min_spp: 4, max_spp: 64, threshold: 0.005 }
+    }
+}
+
+/* Add one jittered sample to every pixel of the framebuffer. Calling this
+repeatedly progressively refines the image. */
+pub fn render_sample_pass<R: Rng>(
+    framebuffer: &mut Framebuffer,
+    viewport: &Viewport,
+    camera: &Camera,
+    intersectables: &Bvh,
+    shading: &Shading,
+    rng: &mut R
+) {
+    // Neighbouring pixels of a row are traced as a packet, unless the
+    // view is about what a single ray costs.
+    let per_ray = matches!(shading.mode, ShadingMode::IntersectionTests | ShadingMode::RenderTime);
+    for y in 0..framebuffer.height {
+        for x in (0..framebuffer.width).step_by(Lanes::WIDTH) {
+            let count = (framebuffer.width - x).min(Lanes::WIDTH as i32) as usize;
+            let mut shoot = |i: usize| viewport.get_ray(camera, x + i as i32, y, rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5));
+            let mut rays = [shoot(0); Lanes::WIDTH];
+            for (i, r) in rays.iter_mut().enumerate().take(count).skip(1) {
+                *r = shoot(i);
+            }
+            let rays = &rays[..count];
+            if per_ray {
+                for (i, ray) in rays.iter().enumerate() {
+                    framebuffer.add_sample(x + i as i32, y, ray_color(ray, intersectables, shading));
+                }
+                continue;
+            }
+            let start = Instant::now();
+            let traces = trace_packet(&RayPacket::new(rays), intersectables);
+            let elapsed = start.elapsed() / rays.len() as u32;
+            for (i, (ray, trace)) in rays.iter().zip(&traces).enumerate() {
+                framebuffer.add_sample(x + i as i32, y, shade(ray, trace, elapsed, intersectables, shading));
+            }
+        }
+    }
+}
+
+/* Render the scene into the framebuffer, spending samples where the image is
+still noisy. Samples are jittered within the pixel so edges get anti-aliased. */
+pub fn render_adaptive<R: Rng>(
+    framebuffer: &mut Framebuffer,
+    viewport: &Viewport,
+    camera: &Camera,
+    intersectables: &Bvh,
+    settings: &AdaptiveSampling,
+    rng: &mut R
+) {
+    for y in 0..framebuffer.height {
+        for x in 0..framebuffer.width {
+            loop {
+                let stats = framebuffer.get(x, y);
+                if stats.samples >= settings.max_spp {
+                    break;
+                }
+                if stats.samples >= settings.min_spp && stats.standard_error() <= settings.threshold {
+                    break;
+                }
+
+                let ray = viewport.get_ray(camera, x, y, rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5));
+                framebuffer.add_sample(x, y, ray_color(&ray, intersectables, &Shading::default()));
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    use crate::camera::Window;
+    use crate::material::Surface;
+    use crate::point::Point3;
+    use crate::sphere::Sphere;
+    use crate::vec::Vec3;
+
+    #[test]
+    fn test_trace_finds_closest_object() {
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
+        let intersectables = Bvh::new(vec![
+            Box::new(Sphere{ origin: Point3::new(0.0, 0.0, -10.0), radius: 1.0, color: Some(color), material: None, surface: Surface::Color(color) }),
+            Box::new(Sphere{ origin: Point3::new(0.0, 0.0, -5.0), radius: 1.0, color: Some(color), material: None, surface: Surface::Color(color) }),
+            Box::new(Sphere{ origin: Point3::new(20.0, 0.0, -5.0), radius: 1.0, color: Some(color), material: None, surface: Surface::Color(color) }),
+            Box::new(Sphere{ origin: Point3::new(20.0, 0.0, -10.0), radius: 1.0, color: Some(color), material: None, surface: Surface::Color(color) })
+        ]);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let trace = trace(&ray, &intersectables);
+
+        assert_eq!(trace.hit.map(|(i, _)| i), Some(1));
+        // the spheres off to the side are left out after the test against
+        // their box, the two in front of the ray are tested twice each
+        assert_eq!(trace.tests, 3 + 4);
+
+        let packet = trace_packet(&RayPacket::new(&[ray]), &intersectables);
+        assert_eq!(packet[0].hit.map(|(i, _)| i), Some(1));
+        assert_eq!(packet[0].tests, 3 + 2);
+    }
+
+    #[test]
+    fn test_normals_view_shows_the_outward_normal() {
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
+        let intersectables = Bvh::new(vec![
+            Box::new(Sphere{ origin: Point3::new(0.0, 0.0, 0.0), radius: 1.0, color: Some(color), material: None, surface: Surface::Color(color) })
+        ]);
+        // from the inside the far wall faces away along -z
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let inside = ray_color(&ray, &intersectables, &Shading::default());
+        assert!(inside.b < 1.0 && (inside.r - 127.995).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_adaptive_sampling_spends_samples_on_edges() {
+        let window = Window::new(64);
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0));
+        let viewport = Viewport::new(&window, &camera);
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
+        let intersectables = Bvh::new(vec![
+            Box::new(Sphere{ origin: Point3::new(0.0, 0.0, -5.0), radius: 1.5, color: Some(color), material: None, surface: Surface::Color(color) })
+        ]);
+        let settings = AdaptiveSampling { min_spp: 4, max_spp: 32, threshold: 0.01 };
+
+        let mut framebuffer = Framebuffer::new(window.width, window.height);
+        let mut rng = StdRng::seed_from_u64(0);
+        render_adaptive(&mut framebuffer, &viewport, &camera, &intersectables, &settings, &mut rng);
+
+        // The sky in the corner is smooth and should stop early,
+        // somewhere around the silhouette of the sphere we need more.
+        assert_eq!(framebuffer.get(0, 0).samples, settings.min_spp);
+        let max_spent = (0..window.width)
+            .map(|x| framebuffer.get(x, window.height / 2).samples)
+            .max()
+            .unwrap();
+        assert!(max_spent > settings.min_spp);
+    }
+}
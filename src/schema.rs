@@ -0,0 +1,273 @@
+use std::error::Error;
+use std::fmt;
+
+use serde_json::Value;
+
+
+// The version written by this build of the raytracer. Older files are
+// migrated to this version before they are deserialized.
+pub const CURRENT_VERSION: &str = "1.0";
+
+// Every migration upgrades a scene by exactly one version, so a file
+// is brought up to date by applying them in order. Adding optional
+// fields keeps older files valid and needs no new version, only changes
+// that break them like renaming a field do. There have been none yet.
+type Migration = fn(&mut Value);
+
+const MIGRATIONS: [(&str, &str, Migration); 0] = [];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    // A single step into a JSON document, either the member of an
+    // object or the element of an array.
+    Key(String),
+    Index(usize)
+}
+
+pub fn format_path(path: &[Segment]) -> String {
+    let mut formatted = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if formatted.is_empty() => formatted.push_str(key),
+            Segment::Key(key) => formatted.push_str(&format!(".{}", key)),
+            Segment::Index(index) => formatted.push_str(&format!("[{}]", index))
+        }
+    }
+    formatted
+}
+
+
+#[derive(Debug)]
+pub struct SchemaError {
+    // Describes what is wrong with a scene file and where. The line
+    // is looked up in the original source once the error is known.
+    pub path: Vec<Segment>,
+    pub line: Option<usize>,
+    pub message: String
+}
+
+impl SchemaError {
+    pub fn new(path: Vec<Segment>, message: String) -> Self {
+        Self { path, line: None, message }
+    }
+
+    /* Find the line of the error in the source, or of the closest parent that exists there. */
+    pub fn locate(mut self, source: &str) -> Self {
+        if self.line.is_none() {
+            self.line = (0..=self.path.len()).rev().find_map(|n| locate_line(source, &self.path[..n]));
+        }
+        self
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.path.is_empty(), self.line) {
+            (false, Some(line)) => write!(f, "{} (line {}): {}", format_path(&self.path), line, self.message),
+            (false, None) => write!(f, "{}: {}", format_path(&self.path), self.message),
+            (true, Some(line)) => write!(f, "line {}: {}", line, self.message),
+            (true, None) => f.write_str(&self.message)
+        }
+    }
+}
+
+impl Error for SchemaError {}
+
+
+/* Upgrade a parsed scene file to the current version. */
+pub fn migrate(scene: &mut Value) -> Result<(), SchemaError> {
+    upgrade(scene, &MIGRATIONS)
+}
+
+fn upgrade(scene: &mut Value, migrations: &[(&str, &str, Migration)]) -> Result<(), SchemaError> {
+    let version_path = vec![Segment::Key("version".to_string())];
+    let mut version = match scene.get("version") {
+        Some(Value::String(version)) => version.clone(),
+        Some(_) => return Err(SchemaError::new(version_path, "the version must be a string like \"1.0\"".to_string())),
+        None => return Err(SchemaError::new(Vec::new(), "missing field `version`".to_string()))
+    };
+
+    for &(from, to, migration) in migrations {
+        if version == from {
+            migration(scene);
+            version = to.to_string();
+        }
+    }
+
+    if version != CURRENT_VERSION {
+        let known: Vec<&str> = migrations.iter().map(|(from, _, _)| *from).chain([CURRENT_VERSION]).collect();
+        let message = format!("unsupported version \"{}\", expected one of {}", version, known.join(", "));
+        return Err(SchemaError::new(version_path, message));
+    }
+    scene["version"] = Value::String(version);
+    Ok(())
+}
+
+
+struct Scanner<'a> {
+    // Just enough of a JSON parser to walk a document along a path
+    // while counting lines. The document is known to be valid JSON.
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) {
+        if self.peek() == Some(b'\n') {
+            self.line += 1;
+        }
+        self.pos += 1;
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.bump();
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.peek() != Some(b'"') {
+            return None;
+        }
+        self.bump();
+        let start = self.pos;
+        while self.peek()? != b'"' {
+            if self.peek() == Some(b'\\') {
+                self.bump();
+            }
+            self.bump();
+        }
+        let raw = std::str::from_utf8(&self.bytes[start..self.pos]).ok()?;
+        self.bump();
+        // Keys with escapes are rare, let serde_json decode them.
+        serde_json::from_str(&format!("\"{}\"", raw)).ok()
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'"' => { self.string()?; },
+            b'{' | b'[' => {
+                let mut depth = 0;
+                loop {
+                    match self.peek()? {
+                        b'"' => { self.string()?; continue; },
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => depth -= 1,
+                        _ => ()
+                    }
+                    self.bump();
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            },
+            _ => {
+                while !matches!(self.peek()?, b',' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n') {
+                    self.bump();
+                }
+            }
+        }
+        Some(())
+    }
+
+    /* Walk into the value at the current position and return the line the path ends on. */
+    fn find(&mut self, path: &[Segment]) -> Option<usize> {
+        self.skip_whitespace();
+        let Some((segment, rest)) = path.split_first() else {
+            return Some(self.line);
+        };
+
+        let (open, close) = match segment {
+            Segment::Key(_) => (b'{', b'}'),
+            Segment::Index(_) => (b'[', b']')
+        };
+        if self.peek()? != open {
+            return None;
+        }
+        self.bump();
+
+        let mut index = 0;
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == close {
+                return None;
+            }
+            let found = match segment {
+                Segment::Key(key) => {
+                    let member = self.string()?;
+                    self.skip_whitespace();
+                    self.bump(); // the colon
+                    member == *key
+                },
+                Segment::Index(target) => index == *target
+            };
+            if found {
+                return self.find(rest);
+            }
+            self.skip_whitespace();
+            self.skip_value()?;
+            self.skip_whitespace();
+            if self.peek()? == b',' {
+                self.bump();
+            }
+            index += 1;
+        }
+    }
+}
+
+/* The line number, starting at 1, of the value a path points at in a JSON document. */
+pub fn locate_line(source: &str, path: &[Segment]) -> Option<usize> {
+    let mut scanner = Scanner { bytes: source.as_bytes(), pos: 0, line: 1 };
+    scanner.find(path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_locate_line() {
+        let source = "{\n  \"version\": \"1.0\",\n  \"spheres\": [\n    { \"radius\": 1 },\n    {\n      \"radius\": -1\n    }\n  ]\n}";
+        let path = vec![Segment::Key("spheres".to_string()), Segment::Index(1), Segment::Key("radius".to_string())];
+
+        assert_eq!(locate_line(source, &path), Some(6));
+        assert_eq!(locate_line(source, &path[..2]), Some(5));
+        assert_eq!(locate_line(source, &[Segment::Key("planes".to_string())]), None);
+        assert_eq!(format_path(&path), "spheres[1].radius");
+    }
+
+    // A made up older version that called the plane normal direction,
+    // there is no real migration yet to test the machinery with.
+    fn migrate_0_9_to_1_0(scene: &mut Value) {
+        for plane in scene["planes"].as_array_mut().into_iter().flatten().filter_map(Value::as_object_mut) {
+            if let Some(direction) = plane.remove("direction") {
+                plane.insert("orient".to_string(), direction);
+            }
+        }
+    }
+
+    #[test]
+    fn test_migrations_are_applied_in_order() {
+        let migrations: [(&str, &str, Migration); 1] = [("0.9", "1.0", migrate_0_9_to_1_0)];
+        let mut scene = json!({ "version": "0.9", "planes": [{ "direction": { "x": 0.0, "y": 1.0, "z": 0.0 } }] });
+        upgrade(&mut scene, &migrations).unwrap();
+
+        assert_eq!(scene["version"], CURRENT_VERSION);
+        assert!(scene["planes"][0].get("direction").is_none());
+        assert_eq!(scene["planes"][0]["orient"]["y"], 1.0);
+
+        let mut current = json!({ "version": "1.0" });
+        assert!(migrate(&mut current).is_ok());
+        let mut future = json!({ "version": "9.0" });
+        let error = upgrade(&mut future, &migrations).unwrap_err();
+        assert_eq!(error.to_string(), "version: unsupported version \"9.0\", expected one of 0.9, 1.0");
+    }
+}
@@ -0,0 +1,58 @@
+use raylib::prelude::*;
+
+use crate::camera::Window;
+use crate::framebuffer::Framebuffer;
+
+
+pub struct Screen {
+    // The screen presents the framebuffer in the window using a single
+    // texture. The image can be rendered at a lower resolution than the
+    // window, in which case the texture is stretched to fill the window.
+    // The RGBA pixels are kept around so no allocation is needed per frame.
+    pixels: Vec<u8>,
+    texture: Texture2D,
+    render_width: i32,
+    render_height: i32,
+    window_width: i32,
+    window_height: i32
+}
+
+impl Screen {
+    pub fn new(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        window: &Window,
+        render: &Window
+    ) -> Result<Self, String> {
+        let image = Image::gen_image_color(render.width, render.height, Color::BLACK);
+        let texture = rl.load_texture_from_image(thread, &image)?;
+        texture.set_texture_filter(thread, TextureFilter::TEXTURE_FILTER_BILINEAR);
+
+        Ok(Self {
+            pixels: vec![0; (render.width * render.height * 4) as usize],
+            texture,
+            render_width: render.width,
+            render_height: render.height,
+            window_width: window.width,
+            window_height: window.height
+        })
+    }
+
+    /* Copy the current image of the framebuffer to the texture. */
+    pub fn update(&mut self, framebuffer: &Framebuffer) {
+        framebuffer.write_rgba(&mut self.pixels);
+        self.texture.update_texture(&self.pixels);
+    }
+
+    /* Save the image currently on the texture as a PNG file. */
+    pub fn export_png(&self, path: &str) -> Result<(), String> {
+        self.texture.load_image()?.export_image(path);
+        Ok(())
+    }
+
+    pub fn draw<D: RaylibDraw>(&self, d: &mut D) {
+        let source = Rectangle::new(0.0, 0.0, self.render_width as f32, self.render_height as f32);
+        let dest = Rectangle::new(0.0, 0.0, self.window_width as f32, self.window_height as f32);
+        d.draw_texture_pro(&self.texture, source, dest, Vector2::new(0.0, 0.0), 0.0, Color::WHITE);
+    }
+}
@@ -0,0 +1,457 @@
+use std::ops;
+
+use crate::real::Real;
+use crate::vec::Vec3;
+
+
+pub trait Simd: Copy + ops::Add<Output = Self> + ops::Sub<Output = Self> + ops::Mul<Output = Self> + ops::Div<Output = Self> {
+    // A fixed number of Reals that are worked on at once. Every
+    // operation applies to all lanes, comparisons give a bit per lane.
+    const WIDTH: usize;
+
+    fn splat(value: Real) -> Self;
+    fn from_fn<F: FnMut(usize) -> Real>(f: F) -> Self;
+    fn lane(&self, i: usize) -> Real;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn sqrt(self) -> Self;
+    /* Bit i is set where lane i of self is less than lane i of other */
+    fn lt(self, other: Self) -> u32;
+
+    /* Lane i of a where bit i of the mask is set, otherwise lane i of b */
+    fn select(mask: u32, a: Self, b: Self) -> Self {
+        Self::from_fn(|i| if mask & (1 << i) != 0 { a.lane(i) } else { b.lane(i) })
+    }
+
+    /* The mask with a bit for every lane */
+    fn all() -> u32 {
+        (1 << Self::WIDTH) - 1
+    }
+}
+
+
+// The kernels work on plain arrays. The arrays are loaded into registers
+// for every operation, after inlining the compiler keeps them there.
+// SSE2 is part of every x86_64 CPU, AVX is only used when the build
+// enables it, for example with RUSTFLAGS="-C target-cpu=native".
+
+#[cfg(any(test, not(target_arch = "x86_64")))]
+mod scalar {
+    use crate::real::Real;
+
+    macro_rules! binary {
+        ($($name:ident => $op:expr),*) => {$(
+            pub fn $name<const N: usize>(a: [Real; N], b: [Real; N]) -> [Real; N] {
+                std::array::from_fn(|i| $op(a[i], b[i]))
+            }
+        )*}
+    }
+
+    binary!(add => |a, b| a + b, sub => |a, b| a - b, mul => |a, b| a * b, div => |a, b| a / b,
+        min => Real::min, max => Real::max);
+
+    pub fn sqrt<const N: usize>(a: [Real; N]) -> [Real; N] {
+        a.map(Real::sqrt)
+    }
+
+    pub fn lt<const N: usize>(a: [Real; N], b: [Real; N]) -> u32 {
+        (0..N).filter(|&i| a[i] < b[i]).fold(0, |mask, i| mask | 1 << i)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+use scalar as kernel4;
+
+// Four f64 in one AVX register.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx", not(feature = "f32")))]
+mod kernel4 {
+    use std::arch::x86_64::*;
+
+    macro_rules! binary {
+        ($($name:ident => $op:ident),*) => {$(
+            #[inline(always)]
+            pub fn $name(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+                let mut out = [0.0; 4];
+                // SAFETY: AVX is enabled for the build and the arrays hold four lanes.
+                unsafe { _mm256_storeu_pd(out.as_mut_ptr(), $op(_mm256_loadu_pd(a.as_ptr()), _mm256_loadu_pd(b.as_ptr()))) };
+                out
+            }
+        )*}
+    }
+
+    binary!(add => _mm256_add_pd, sub => _mm256_sub_pd, mul => _mm256_mul_pd, div => _mm256_div_pd,
+        min => _mm256_min_pd, max => _mm256_max_pd);
+
+    #[inline(always)]
+    pub fn sqrt(a: [f64; 4]) -> [f64; 4] {
+        let mut out = [0.0; 4];
+        // SAFETY: as above
+        unsafe { _mm256_storeu_pd(out.as_mut_ptr(), _mm256_sqrt_pd(_mm256_loadu_pd(a.as_ptr()))) };
+        out
+    }
+
+    #[inline(always)]
+    pub fn lt(a: [f64; 4], b: [f64; 4]) -> u32 {
+        // SAFETY: as above
+        unsafe { _mm256_movemask_pd(_mm256_cmp_pd::<_CMP_LT_OQ>(_mm256_loadu_pd(a.as_ptr()), _mm256_loadu_pd(b.as_ptr()))) as u32 }
+    }
+}
+
+// Without AVX four f64 take two SSE2 registers.
+#[cfg(all(target_arch = "x86_64", not(target_feature = "avx"), not(feature = "f32")))]
+mod kernel4 {
+    use std::arch::x86_64::*;
+
+    macro_rules! binary {
+        ($($name:ident => $op:ident),*) => {$(
+            #[inline(always)]
+            pub fn $name(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+                let mut out = [0.0; 4];
+                for half in [0, 2] {
+                    // SAFETY: SSE2 is always there on x86_64 and each half holds two lanes.
+                    unsafe {
+                        let result = $op(_mm_loadu_pd(a[half..].as_ptr()), _mm_loadu_pd(b[half..].as_ptr()));
+                        _mm_storeu_pd(out[half..].as_mut_ptr(), result);
+                    }
+                }
+                out
+            }
+        )*}
+    }
+
+    binary!(add => _mm_add_pd, sub => _mm_sub_pd, mul => _mm_mul_pd, div => _mm_div_pd,
+        min => _mm_min_pd, max => _mm_max_pd);
+
+    #[inline(always)]
+    pub fn sqrt(a: [f64; 4]) -> [f64; 4] {
+        let mut out = [0.0; 4];
+        for half in [0, 2] {
+            // SAFETY: as above
+            unsafe { _mm_storeu_pd(out[half..].as_mut_ptr(), _mm_sqrt_pd(_mm_loadu_pd(a[half..].as_ptr()))) };
+        }
+        out
+    }
+
+    #[inline(always)]
+    pub fn lt(a: [f64; 4], b: [f64; 4]) -> u32 {
+        [0, 2].into_iter().fold(0, |mask, half| {
+            // SAFETY: as above
+            let bits = unsafe { _mm_movemask_pd(_mm_cmplt_pd(_mm_loadu_pd(a[half..].as_ptr()), _mm_loadu_pd(b[half..].as_ptr()))) };
+            mask | (bits as u32) << half
+        })
+    }
+}
+
+// Four f32 in one SSE register.
+#[cfg(all(target_arch = "x86_64", feature = "f32"))]
+#[cfg_attr(target_feature = "avx", allow(dead_code))]
+mod kernel4 {
+    use std::arch::x86_64::*;
+
+    macro_rules! binary {
+        ($($name:ident => $op:ident),*) => {$(
+            #[inline(always)]
+            pub fn $name(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+                let mut out = [0.0; 4];
+                // SAFETY: SSE is always there on x86_64 and the arrays hold four lanes.
+                unsafe { _mm_storeu_ps(out.as_mut_ptr(), $op(_mm_loadu_ps(a.as_ptr()), _mm_loadu_ps(b.as_ptr()))) };
+                out
+            }
+        )*}
+    }
+
+    binary!(add => _mm_add_ps, sub => _mm_sub_ps, mul => _mm_mul_ps, div => _mm_div_ps,
+        min => _mm_min_ps, max => _mm_max_ps);
+
+    #[inline(always)]
+    pub fn sqrt(a: [f32; 4]) -> [f32; 4] {
+        let mut out = [0.0; 4];
+        // SAFETY: as above
+        unsafe { _mm_storeu_ps(out.as_mut_ptr(), _mm_sqrt_ps(_mm_loadu_ps(a.as_ptr()))) };
+        out
+    }
+
+    #[inline(always)]
+    pub fn lt(a: [f32; 4], b: [f32; 4]) -> u32 {
+        // SAFETY: as above
+        unsafe { _mm_movemask_ps(_mm_cmplt_ps(_mm_loadu_ps(a.as_ptr()), _mm_loadu_ps(b.as_ptr()))) as u32 }
+    }
+}
+
+// Eight f32 in one AVX register.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx", feature = "f32"))]
+mod kernel8 {
+    use std::arch::x86_64::*;
+
+    macro_rules! binary {
+        ($($name:ident => $op:ident),*) => {$(
+            #[inline(always)]
+            pub fn $name(a: [f32; 8], b: [f32; 8]) -> [f32; 8] {
+                let mut out = [0.0; 8];
+                // SAFETY: AVX is enabled for the build and the arrays hold eight lanes.
+                unsafe { _mm256_storeu_ps(out.as_mut_ptr(), $op(_mm256_loadu_ps(a.as_ptr()), _mm256_loadu_ps(b.as_ptr()))) };
+                out
+            }
+        )*}
+    }
+
+    binary!(add => _mm256_add_ps, sub => _mm256_sub_ps, mul => _mm256_mul_ps, div => _mm256_div_ps,
+        min => _mm256_min_ps, max => _mm256_max_ps);
+
+    #[inline(always)]
+    pub fn sqrt(a: [f32; 8]) -> [f32; 8] {
+        let mut out = [0.0; 8];
+        // SAFETY: as above
+        unsafe { _mm256_storeu_ps(out.as_mut_ptr(), _mm256_sqrt_ps(_mm256_loadu_ps(a.as_ptr()))) };
+        out
+    }
+
+    #[inline(always)]
+    pub fn lt(a: [f32; 8], b: [f32; 8]) -> u32 {
+        // SAFETY: as above
+        unsafe { _mm256_movemask_ps(_mm256_cmp_ps::<_CMP_LT_OQ>(_mm256_loadu_ps(a.as_ptr()), _mm256_loadu_ps(b.as_ptr()))) as u32 }
+    }
+}
+
+// Otherwise eight lanes are two times four. Packets are only eight wide
+// with AVX and single precision, elsewhere this is left for the tests.
+#[cfg(not(all(target_arch = "x86_64", target_feature = "avx", feature = "f32")))]
+#[allow(dead_code)]
+mod kernel8 {
+    use super::kernel4;
+    use crate::real::Real;
+
+    fn halves(a: [Real; 8]) -> ([Real; 4], [Real; 4]) {
+        ([a[0], a[1], a[2], a[3]], [a[4], a[5], a[6], a[7]])
+    }
+
+    fn join(low: [Real; 4], high: [Real; 4]) -> [Real; 8] {
+        [low[0], low[1], low[2], low[3], high[0], high[1], high[2], high[3]]
+    }
+
+    macro_rules! binary {
+        ($($name:ident),*) => {$(
+            #[inline(always)]
+            pub fn $name(a: [Real; 8], b: [Real; 8]) -> [Real; 8] {
+                let ((a0, a1), (b0, b1)) = (halves(a), halves(b));
+                join(kernel4::$name(a0, b0), kernel4::$name(a1, b1))
+            }
+        )*}
+    }
+
+    binary!(add, sub, mul, div, min, max);
+
+    #[inline(always)]
+    pub fn sqrt(a: [Real; 8]) -> [Real; 8] {
+        let (low, high) = halves(a);
+        join(kernel4::sqrt(low), kernel4::sqrt(high))
+    }
+
+    #[inline(always)]
+    pub fn lt(a: [Real; 8], b: [Real; 8]) -> u32 {
+        let ((a0, a1), (b0, b1)) = (halves(a), halves(b));
+        kernel4::lt(a0, b0) | kernel4::lt(a1, b1) << 4
+    }
+}
+
+
+macro_rules! lanes {
+    ($(#[$meta:meta])* $name:ident, $width:expr, $kernel:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub struct $name(pub [Real; $width]);
+
+        impl Simd for $name {
+            const WIDTH: usize = $width;
+
+            fn splat(value: Real) -> Self {
+                Self([value; $width])
+            }
+
+            fn from_fn<F: FnMut(usize) -> Real>(f: F) -> Self {
+                Self(std::array::from_fn(f))
+            }
+
+            fn lane(&self, i: usize) -> Real {
+                self.0[i]
+            }
+
+            fn min(self, other: Self) -> Self {
+                Self($kernel::min(self.0, other.0))
+            }
+
+            fn max(self, other: Self) -> Self {
+                Self($kernel::max(self.0, other.0))
+            }
+
+            fn sqrt(self) -> Self {
+                Self($kernel::sqrt(self.0))
+            }
+
+            fn lt(self, other: Self) -> u32 {
+                $kernel::lt(self.0, other.0)
+            }
+        }
+
+        impl ops::Add for $name {
+            type Output = $name;
+
+            fn add(self, rhs: $name) -> Self::Output {
+                Self($kernel::add(self.0, rhs.0))
+            }
+        }
+
+        impl ops::Sub for $name {
+            type Output = $name;
+
+            fn sub(self, rhs: $name) -> Self::Output {
+                Self($kernel::sub(self.0, rhs.0))
+            }
+        }
+
+        impl ops::Mul for $name {
+            type Output = $name;
+
+            fn mul(self, rhs: $name) -> Self::Output {
+                Self($kernel::mul(self.0, rhs.0))
+            }
+        }
+
+        impl ops::Div for $name {
+            type Output = $name;
+
+            fn div(self, rhs: $name) -> Self::Output {
+                Self($kernel::div(self.0, rhs.0))
+            }
+        }
+    }
+}
+
+lanes!(
+    #[cfg_attr(all(target_arch = "x86_64", target_feature = "avx", feature = "f32"), allow(dead_code))]
+    RealX4, 4, kernel4
+);
+lanes!(
+    #[cfg_attr(not(all(target_arch = "x86_64", target_feature = "avx", feature = "f32")), allow(dead_code))]
+    RealX8, 8, kernel8
+);
+
+// The width of the ray packets, as wide as a register. Only single
+// precision with AVX fits eight lanes.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx", feature = "f32"))]
+pub type Lanes = RealX8;
+#[cfg(not(all(target_arch = "x86_64", target_feature = "avx", feature = "f32")))]
+pub type Lanes = RealX4;
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct Vec3x<L: Simd> {
+    // As many vectors as there are lanes, stored per component so an
+    // operation on the components handles all vectors at once.
+    pub x: L,
+    pub y: L,
+    pub z: L
+}
+
+impl<L: Simd> Vec3x<L> {
+    /* The same vector in every lane */
+    pub fn splat(v: Vec3) -> Self {
+        Self { x: L::splat(v.x), y: L::splat(v.y), z: L::splat(v.z) }
+    }
+
+    pub fn from_fn<F: FnMut(usize) -> Vec3>(mut f: F) -> Self {
+        // No lane type is wider than RealX8, the vectors stay on the stack
+        let mut vectors = [Vec3::new(0.0, 0.0, 0.0); RealX8::WIDTH];
+        for (i, v) in vectors.iter_mut().take(L::WIDTH).enumerate() {
+            *v = f(i);
+        }
+        Self {
+            x: L::from_fn(|i| vectors[i].x),
+            y: L::from_fn(|i| vectors[i].y),
+            z: L::from_fn(|i| vectors[i].z)
+        }
+    }
+
+    pub fn lane(&self, i: usize) -> Vec3 {
+        Vec3::new(self.x.lane(i), self.y.lane(i), self.z.lane(i))
+    }
+
+    pub fn dot(&self, v: &Self) -> L {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+
+    /* Vector perpendicular to both self and v following the right hand rule */
+    pub fn cross(&self, v: &Self) -> Self {
+        Self {
+            x: self.y * v.z - self.z * v.y,
+            y: self.z * v.x - self.x * v.z,
+            z: self.x * v.y - self.y * v.x
+        }
+    }
+}
+
+impl<L: Simd> ops::Add for Vec3x<L> {
+    type Output = Vec3x<L>;
+
+    fn add(self, rhs: Vec3x<L>) -> Self::Output {
+        Self { x: self.x + rhs.x, y: self.y + rhs.y, z: self.z + rhs.z }
+    }
+}
+
+impl<L: Simd> ops::Sub for Vec3x<L> {
+    type Output = Vec3x<L>;
+
+    fn sub(self, rhs: Vec3x<L>) -> Self::Output {
+        Self { x: self.x - rhs.x, y: self.y - rhs.y, z: self.z - rhs.z }
+    }
+}
+
+// Implement Vec3x * L, scaling every vector by its own lane
+impl<L: Simd> ops::Mul<L> for Vec3x<L> {
+    type Output = Vec3x<L>;
+
+    fn mul(self, rhs: L) -> Self::Output {
+        Self { x: self.x * rhs, y: self.y * rhs, z: self.z * rhs }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_kernels<L: Simd, const N: usize>(a: [Real; N], b: [Real; N]) {
+        let (x, y) = (L::from_fn(|i| a[i]), L::from_fn(|i| b[i]));
+        let lanes = |v: L| -> [Real; N] { std::array::from_fn(|i| v.lane(i)) };
+
+        assert_eq!(lanes(x + y), scalar::add(a, b));
+        assert_eq!(lanes(x - y), scalar::sub(a, b));
+        assert_eq!(lanes(x * y), scalar::mul(a, b));
+        assert_eq!(lanes(x / y), scalar::div(a, b));
+        assert_eq!(lanes(x.min(y)), scalar::min(a, b));
+        assert_eq!(lanes(x.max(y)), scalar::max(a, b));
+        assert_eq!(lanes(y.sqrt()), scalar::sqrt(b));
+        assert_eq!(x.lt(y), scalar::lt(a, b));
+        assert_eq!(L::select(0b0101, x, y).lane(1), b[1]);
+    }
+
+    #[test]
+    fn test_kernels_agree_with_scalar_math() {
+        let a = [1.5, -2.0, 3.25, 0.0, 7.0, -0.5, 2.0, 9.0];
+        let b = [0.5, 4.0, 3.25, 2.0, 1.0, 0.25, 6.0, 3.0];
+        check_kernels::<RealX4, 4>([a[0], a[1], a[2], a[3]], [b[0], b[1], b[2], b[3]]);
+        check_kernels::<RealX8, 8>(a, b);
+    }
+
+    #[test]
+    fn test_vectors_in_lanes() {
+        let a = Vec3x::<RealX4>::from_fn(|i| Vec3::new(i as Real, 1.0, 0.0));
+        let b = Vec3x::splat(Vec3::new(0.0, 0.0, 1.0));
+        let cross = a.cross(&b);
+        for i in 0..4 {
+            let expected = a.lane(i).cross(&b.lane(i));
+            assert!((cross.lane(i) - expected).near_zero());
+            assert_eq!(a.dot(&a).lane(i), a.lane(i).dot(&a.lane(i)));
+        }
+    }
+}
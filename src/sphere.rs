@@ -1,68 +1,33 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+use crate::color::RGBAColor;
 use crate::point::Point3;
+use crate::quadratic::{solve_lanes, Quadratic};
 use crate::ray::Ray;
 use crate::hit::Hit;
+use crate::material::Surface;
+use crate::normal::Normal3;
+use crate::packet::RayPacket;
+use crate::real::Real;
+use crate::simd::{Simd, Vec3x};
 use crate::traits::Intersectable;
 use crate::vec::Vec3;
 
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Sphere {
     pub origin: Point3,
-    pub radius: f64,
-}
-
-// TODO Create Vec4 later -> Homogeneous coordinates
-// Non-Homogeneous -> Matrix * point + vec
-// Homogeneous -> Matrix * Point
-
-struct Quadratic {
-    discriminant: f64,
-    solutions: Option<[f64;2]>
-}
-
-impl Quadratic {
-    pub fn new(a: f64, b: f64, c: f64) -> Self {
-        let discriminant = b.powf(2.0) - 4.0 * a * c;
-        match discriminant {
-            _ if discriminant < 0.0 => {
-                Self { discriminant, solutions: None }
-            },
-            _ if discriminant == 0.0 => {
-                let t = -b / (2.0 * a);
-                Self { discriminant, solutions: Some([t, f64::INFINITY]) }
-            },
-            _ => {
-                let t0 = -b + (b.powf(2.0) - 4.0 * a * c).sqrt() / (2.0 * a);
-                let t1 = b + (b.powf(2.0) - 4.0 * a * c).sqrt() / (2.0 * a);
-                Self {
-                    discriminant,
-                    solutions: Some([t0.max(t1), t0.min(t1)])
-                }
-            }
-        }
-    }
-
-    pub fn new_with_discriminant(a: f64, h: f64, discriminant: f64) -> Self {
-         match discriminant {
-            _ if discriminant < 0.0 => {
-                Self { discriminant, solutions: None }
-            },
-            _ if discriminant == 0.0 => {
-                let t = h / a;
-                Self { discriminant, solutions: Some([t, f64::INFINITY]) }
-            },
-            _ => {
-                let t0 = h + discriminant.sqrt() / a;
-                let t1 = h + discriminant.sqrt() / a;
-                Self {
-                    discriminant,
-                    solutions: Some([t0.max(t1), t0.min(t1)])
-                }
-            }
-        }
-    }
+    pub radius: Real,
+    // The scene file gives either an inline color or the name of a
+    // material. The surface is resolved from those when the scene is
+    // built, see Config::intersectables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<RGBAColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(skip)]
+    pub surface: Surface
 }
 
 /*
@@ -84,15 +49,22 @@ impl Sphere {
         Quadratic::new(a, b, c)
     }
 
-    pub fn get_quadratic_v2(&self, r: &Ray) -> Quadratic {
-        // 6.2 Simplifying the Ray-Sphere Intersection Code
-        // https://raytracing.github.io/books/RayTracingInOneWeekend.html#rays,asimplecamera,andbackground
-        let cq = self.origin - r.origin;
-        let a = r.direction.magnitude().powf(2.0);
-        let h = r.direction.dot(&cq);
-        let c = cq.magnitude().powf(2.0) - self.radius.powf(2.0);
-        let discriminant = h * h - a * c;
-        Quadratic::new_with_discriminant(a, h, discriminant)
+    fn hit_at(&self, r: &Ray, t: Real) -> Hit {
+        Hit::facing(r, t, Normal3::from((r.at(t) - self.origin).normalise()))
+    }
+
+    /* The distance to the closest hit in front of every ray of a packet, infinity where the ray misses. */
+    pub fn packet_distances<L: Simd>(&self, packet: &RayPacket<L>) -> L {
+        // The directions are of unit length so a is 1, see get_quadratic.
+        let cq = Vec3x::splat(self.origin.to_vec()) - packet.origin;
+        let half_b = L::splat(0.0) - packet.direction.dot(&cq);
+        let c = cq.dot(&cq) - L::splat(self.radius * self.radius);
+        let (near, far, roots) = solve_lanes(half_b, c);
+        // From inside the sphere the near hit lies behind the origin.
+        let zero = L::splat(0.0);
+        let t = L::select(zero.lt(near), near, far);
+        let hits = roots & zero.lt(t);
+        L::select(hits, t, L::splat(Real::INFINITY))
     }
 
     
@@ -115,18 +87,35 @@ impl Intersectable for Sphere {
         //    In this case None will be returned
         // 4. The ray is inside of the sphere and intersects only once
         //    In this case the vector will contain one hit
-        let quadratic = self.get_quadratic_v2(r);
-        quadratic.solutions.map(|solutions| {
+        self.get_quadratic(r).roots.map(|solutions| {
             solutions
                 .into_iter()
-                .map(|t| {
-                    let p = r.at(t);
-                    let normal = (p - self.origin).normalise();
-                    Hit{ t, p, normal }
-                })
+                .map(|t| self.hit_at(r, t))
                 .collect()
         })
     }
+
+    fn bounding_box(&self) -> Option<(Point3, Point3)> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some((self.origin - r, self.origin + r))
+    }
+
+    fn color_at(&self, hit: &Hit) -> RGBAColor {
+        self.surface.color_at(hit)
+    }
+
+    fn intersect_packet(&self, packet: &RayPacket, closest: &mut [Option<Hit>]) -> u32 {
+        let distances = self.packet_distances(packet);
+        let mut replaced = 0;
+        for i in packet.lanes(u32::MAX) {
+            let t = distances.lane(i);
+            if t < closest[i].map_or(Real::INFINITY, |c| c.t) {
+                closest[i] = Some(self.hit_at(&packet.ray(i), t));
+                replaced |= 1 << i;
+            }
+        }
+        replaced
+    }
 }
 
 
@@ -135,18 +124,49 @@ mod tests {
 
     use super::*;
 
+    use crate::simd::{RealX4, RealX8};
+
     #[test]
     fn test_intersect() {
         let ray = Ray::new(
             Point3{x: 0.0, y: 0.0, z: 0.0},
             Vec3{x: 1.0, y: 0.0, z: 0.0}
         );
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
         let sphere = Sphere{
             origin: Point3::new(5.0, 0.0, 0.0),
-            radius: 1.0
+            radius: 1.0,
+            color: Some(color),
+            material: None,
+            surface: Surface::Color(color)
         };
         let intersect = sphere.intersect(&ray);
         assert!(intersect);
     }
+
+    #[test]
+    fn test_packet_distances() {
+        let color = RGBAColor::new(0.0, 255.0, 0.0).unwrap();
+        let sphere = Sphere {
+            origin: Point3::new(5.0, 0.0, 0.0),
+            radius: 1.0,
+            color: Some(color),
+            material: None,
+            surface: Surface::Color(color)
+        };
+        let rays = [
+            Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+            Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)),
+            Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0))
+        ];
+        let expected = [4.0, Real::INFINITY, 1.0, Real::INFINITY];
+
+        let distances = sphere.packet_distances(&RayPacket::<RealX4>::new(&rays));
+        let wide = sphere.packet_distances(&RayPacket::<RealX8>::new(&rays));
+        for (i, t) in expected.into_iter().enumerate() {
+            assert!(distances.lane(i) == t && wide.lane(i) == t);
+        }
+    }
 }
 
@@ -0,0 +1,96 @@
+use crate::mesh::{Mesh, MeshFileError};
+use crate::point::Point3;
+use crate::real::Real;
+
+
+/* Binary files start with an 80 byte header and the number of triangles, followed by 50 bytes per triangle.
+ASCII files start with "solid", but so do the headers of some binary files, so the size decides. */
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    !bytes.starts_with(b"solid") || 84 + count * 50 == bytes.len()
+}
+
+fn read_binary(bytes: &[u8]) -> Result<Mesh, MeshFileError> {
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    if bytes.len() < 84 + count * 50 {
+        return Err(MeshFileError(format!("the file should hold {} triangles but ends early", count)));
+    }
+    let float = |at: usize| f32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as Real;
+
+    let mut mesh = Mesh::default();
+    for t in 0..count {
+        // Every triangle starts with its normal, which is left out as the
+        // corners already give the facing.
+        let start = 84 + t * 50 + 12;
+        for corner in 0..3 {
+            let at = start + corner * 12;
+            mesh.vertices.push(Point3::new(float(at), float(at + 4), float(at + 8)));
+        }
+        mesh.triangles.push([3 * t, 3 * t + 1, 3 * t + 2]);
+    }
+    Ok(mesh)
+}
+
+fn read_ascii(bytes: &[u8]) -> Result<Mesh, MeshFileError> {
+    let source = String::from_utf8_lossy(bytes);
+    let mut words = source.split_whitespace();
+    let mut mesh = Mesh::default();
+    while let Some(word) = words.next() {
+        if word != "vertex" {
+            continue;
+        }
+        let mut coordinate = || -> Result<Real, MeshFileError> {
+            let word = words.next().unwrap_or("");
+            word.parse().map_err(|_| MeshFileError(format!("expected a coordinate, got \"{}\"", word)))
+        };
+        mesh.vertices.push(Point3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+    if mesh.vertices.len() % 3 != 0 {
+        return Err(MeshFileError(format!("{} vertices do not make whole triangles", mesh.vertices.len())));
+    }
+    mesh.triangles = (0..mesh.vertices.len() / 3).map(|t| [3 * t, 3 * t + 1, 3 * t + 2]).collect();
+    Ok(mesh)
+}
+
+/* Read an STL file, every triangle gets its own three vertices since the format does not share them. */
+pub fn read_stl(bytes: &[u8]) -> Result<Mesh, MeshFileError> {
+    if is_binary(bytes) { read_binary(bytes) } else { read_ascii(bytes) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_and_binary_files_agree() {
+        let ascii = b"solid part
+facet normal 0 0 1
+    outer loop
+        vertex 0 0 0
+        vertex 1 0 0
+        vertex 0 1 0
+    endloop
+endfacet
+endsolid part
+";
+        let mesh = read_stl(ascii).unwrap();
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+        assert_eq!(mesh.vertices[2].y, 1.0);
+
+        // A header starting with "solid" must not fool the reader.
+        let mut binary = b"solid exported by a CAD tool".to_vec();
+        binary.resize(80, 0);
+        binary.extend(1u32.to_le_bytes());
+        for value in [0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            binary.extend(value.to_le_bytes());
+        }
+        binary.extend([0, 0]);
+        let from_binary = read_stl(&binary).unwrap();
+        assert_eq!(from_binary.triangles, mesh.triangles);
+        assert_eq!(from_binary.vertices[2].y, 1.0);
+    }
+}
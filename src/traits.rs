@@ -1,6 +1,36 @@
-use crate::{hit::Hit, ray::Ray};
+use crate::{color::RGBAColor, hit::Hit, packet::RayPacket, point::Point3, ray::Ray};
 
 pub trait Intersectable {
     fn intersect(&self, ray: &Ray) -> bool;
     fn intersect_hits(&self, ray: &Ray) -> Option<Vec<Hit>>;
+    fn color_at(&self, hit: &Hit) -> RGBAColor;
+    /* The lowest and highest corner of a box around the object, None for objects without an end like planes. */
+    fn bounding_box(&self) -> Option<(Point3, Point3)>;
+
+    /* Replace the closest hits of the rays in a packet, one per lane, wherever this object is hit in front of the
+    ray and closer by. Returns the mask of the lanes that were replaced. Tests the rays one at a time unless the
+    object has a packet version. */
+    fn intersect_packet(&self, packet: &RayPacket, closest: &mut [Option<Hit>]) -> u32 {
+        let mut replaced = 0;
+        for i in packet.lanes(u32::MAX) {
+            let ray = packet.ray(i);
+            if !self.intersect(&ray) {
+                continue;
+            }
+            if let Some(h) = self.nearest_hit(&ray).filter(|h| closest[i].is_none_or(|c| h.t < c.t)) {
+                closest[i] = Some(h);
+                replaced |= 1 << i;
+            }
+        }
+        replaced
+    }
+
+    /* The closest of the exact hits in front of the ray origin, for a ray that passed the quick test. */
+    fn nearest_hit(&self, ray: &Ray) -> Option<Hit> {
+        self.intersect_hits(ray)
+            .into_iter()
+            .flatten()
+            .filter(|h| h.t > 0.0)
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
 }
@@ -0,0 +1,215 @@
+use std::ops;
+
+use crate::matrix::Mat4;
+use crate::normal::Normal3;
+use crate::point::Point3;
+use crate::quaternion::{Quaternion, Rotation};
+use crate::real::Real;
+use crate::vec::Vec3;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    // An affine transform together with its inverse. The inverse is
+    // needed for normals and to bring rays into the space of an object,
+    // the constructors know it without solving for it.
+    matrix: Mat4,
+    inverse: Mat4
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self { matrix: Mat4::IDENTITY, inverse: Mat4::IDENTITY };
+
+    /* Wrap a matrix, None when it cannot be inverted. */
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        Some(Self { matrix, inverse: matrix.inverse()? })
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self { matrix: self.inverse, inverse: self.matrix }
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let matrix = |v: Vec3| Mat4::new([
+            [1.0, 0.0, 0.0, v.x],
+            [0.0, 1.0, 0.0, v.y],
+            [0.0, 0.0, 1.0, v.z],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        Self { matrix: matrix(offset), inverse: matrix(-offset) }
+    }
+
+    /* Scale along the axes, None when one of the factors is zero or not a finite number. */
+    pub fn scale(factors: Vec3) -> Option<Self> {
+        if [factors.x, factors.y, factors.z].iter().any(|f| *f == 0.0 || !f.is_finite()) {
+            return None;
+        }
+        let matrix = |x: Real, y: Real, z: Real| Mat4::new([
+            [x, 0.0, 0.0, 0.0],
+            [0.0, y, 0.0, 0.0],
+            [0.0, 0.0, z, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        Some(Self {
+            matrix: matrix(factors.x, factors.y, factors.z),
+            inverse: matrix(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z)
+        })
+    }
+
+    /* Scale, then rotate, then move, the way includes and instances place objects. The scale is uniform and must
+    be finite and positive, scene files are validated for that before they are placed. */
+    pub fn from_parts(translate: Option<Vec3>, rotate: Option<Rotation>, scale: Option<Real>) -> Self {
+        let scale = scale.map_or(Self::IDENTITY, |s| Self::scale(Vec3::new(s, s, s)).expect("scales are validated to be finite and positive"));
+        let rotate = match rotate {
+            Some(Rotation::AxisAngle { axis, angle }) => Self::rotate(axis, angle),
+            Some(rotation) => Self::rotation(rotation.quaternion()),
+            None => Self::IDENTITY
+        };
+        let translate = translate.map_or(Self::IDENTITY, Self::translate);
+        translate * rotate * scale
+    }
+
+    pub fn rotation(q: Quaternion) -> Self {
+        let matrix = q.normalise().to_mat4();
+        // The inverse of a rotation is its transpose
+        Self { matrix, inverse: matrix.transpose() }
+    }
+
+    /* Rotate counter clockwise around an axis through the origin, seen from the tip of the axis. The angle is in radians. */
+    pub fn rotate(axis: Vec3, angle: Real) -> Self {
+        Self::rotation(Quaternion::from_axis_angle(axis, angle))
+    }
+
+    /* Place something at eye facing target, like the camera its negative z axis points forward and its y axis
+    leans towards up. Returns None when eye and target are the same point or up runs along the view direction. */
+    pub fn look_at(eye: Point3, target: Point3, up: Vec3) -> Option<Self> {
+        let forward = target - eye;
+        let right = forward.cross(&up);
+        if forward.magnitude() < 1e-12 || right.magnitude() < 1e-12 {
+            return None;
+        }
+        let (forward, right) = (forward.normalise(), right.normalise());
+        let up = right.cross(&forward);
+        let back = -forward;
+
+        let matrix = Mat4::new([
+            [right.x, up.x, back.x, eye.x],
+            [right.y, up.y, back.y, eye.y],
+            [right.z, up.z, back.z, eye.z],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        let inverse = Mat4::new([
+            [right.x, right.y, right.z, -right.dot(&eye.to_vec())],
+            [up.x, up.y, up.z, -up.dot(&eye.to_vec())],
+            [back.x, back.y, back.z, -back.dot(&eye.to_vec())],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        Some(Self { matrix, inverse })
+    }
+
+    pub fn point(&self, p: &Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn direction(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /* Normals go through the inverse transpose, so they stay perpendicular to the surface under a non uniform scale.
+    The result is normalised. */
+    pub fn normal(&self, n: &Normal3) -> Normal3 {
+        Normal3::from(self.inverse.transpose().transform_vector(&n.to_vec()).normalise())
+    }
+
+    /* True for mirroring transforms, which turn the winding of triangles around. */
+    pub fn swaps_handedness(&self) -> bool {
+        self.matrix.determinant() < 0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Frame {
+    // A place in a parent: scaled, rotated and then moved. Includes,
+    // instances and nodes are all placed like this, and a frame within
+    // a frame is again a single frame.
+    pub translate: Option<Vec3>,
+    pub rotate: Option<Rotation>,
+    pub scale: Option<Real>
+}
+
+impl Frame {
+    pub fn transform(&self) -> Transform {
+        Transform::from_parts(self.translate, self.rotate, self.scale)
+    }
+
+    /* The same place seen from the space the parent is placed in. */
+    pub fn within(&self, parent: &Frame) -> Frame {
+        let origin = Point3::ORIGIN + self.translate.unwrap_or(Vec3::new(0.0, 0.0, 0.0));
+        Frame {
+            translate: Some(parent.transform().point(&origin).to_vec()),
+            rotate: match (parent.rotate, self.rotate) {
+                (Some(outer), Some(inner)) => Some(outer.after(&inner)),
+                (outer, inner) => outer.or(inner)
+            },
+            scale: Some(self.scale.unwrap_or(1.0) * parent.scale.unwrap_or(1.0))
+        }
+    }
+}
+
+// Implement Transform * Transform, the right transform is applied first
+impl ops::Mul<Transform> for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Self::Output {
+        Self { matrix: self.matrix * rhs.matrix, inverse: rhs.inverse * self.inverse }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::real::consts::FRAC_PI_2;
+    use crate::real::TOLERANCE;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < TOLERANCE, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_at(a: Point3, b: Point3) {
+        assert!(a.distance(&b) < TOLERANCE, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_points_directions_and_normals() {
+        let t = Transform::translate(Vec3::new(1.0, 2.0, 3.0)) * Transform::rotate(Vec3::new(0.0, 0.0, 1.0), FRAC_PI_2);
+        assert_at(t.point(&Point3::new(1.0, 0.0, 0.0)), Point3::new(1.0, 3.0, 3.0));
+        // directions are not moved
+        assert_close(t.direction(&Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+        assert_at(t.inverse().point(&Point3::new(1.0, 3.0, 3.0)), Point3::new(1.0, 0.0, 0.0));
+
+        // Squashing a 45 degree slope makes it flatter, so its normal points more upwards
+        let squash = Transform::scale(Vec3::new(1.0, 0.5, 1.0)).unwrap();
+        let normal = squash.normal(&Normal3::new(-1.0, 1.0, 0.0));
+        assert_close(normal.to_vec(), Vec3::new(-1.0, 2.0, 0.0).normalise());
+        assert!(Transform::scale(Vec3::new(0.0, 1.0, 1.0)).is_none());
+        assert!(Transform::scale(Vec3::new(-1.0, 1.0, 1.0)).unwrap().swaps_handedness());
+    }
+
+    #[test]
+    fn test_look_at_faces_the_target() {
+        let eye = Point3::new(0.0, 0.0, 5.0);
+        let t = Transform::look_at(eye, Point3::new(5.0, 0.0, 5.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert_close(t.direction(&Vec3::new(0.0, 0.0, -1.0)), Vec3::new(1.0, 0.0, 0.0));
+        assert_at(t.point(&Point3::new(0.0, 0.0, 0.0)), eye);
+        assert_at(t.inverse().point(&eye), Point3::new(0.0, 0.0, 0.0));
+        // the inverse worked out by look_at matches the solved one
+        assert_at(Transform::new(*t.matrix()).unwrap().inverse().point(&Point3::new(1.0, 2.0, 3.0)), t.inverse().point(&Point3::new(1.0, 2.0, 3.0)));
+        assert!(Transform::look_at(eye, eye, Vec3::new(0.0, 1.0, 0.0)).is_none());
+    }
+}
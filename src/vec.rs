@@ -1,45 +1,111 @@
 use std::ops;
 
-use serde::Deserialize;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Clone, Copy)]
-pub struct Vec3 {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64
+use crate::normal::Normal3;
+use crate::real::Real;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Vec3<T = Real> {
+    pub x: T,
+    pub y: T,
+    pub z: T
 }
 
-impl Vec3 {
+impl<T: Float> Vec3<T> {
 
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: T, y: T, z: T) -> Self {
         Self{x, y, z}
     }
 
+    /* The same vector in another float type, like f32 for colors */
+    pub fn cast<U: Float>(self) -> Vec3<U> {
+        Vec3::new(U::from(self.x).unwrap(), U::from(self.y).unwrap(), U::from(self.z).unwrap())
+    }
+
     /* Calculate the vector length (or magnitude) */
-    pub fn magnitude(&self) -> f64 {
+    pub fn magnitude(&self) -> T {
         (self.x.powi(2) + self.y.powi(2) + self.z.powi(2)).sqrt()
     }
 
+    /* The unit vector in the same direction. A zero vector has no direction and is returned as it is,
+    use try_normalise to find out. */
     pub fn normalise(&self) -> Self {
+        self.try_normalise().unwrap_or(*self)
+    }
+
+    /* The unit vector in the same direction, or None for a zero vector. */
+    pub fn try_normalise(&self) -> Option<Self> {
         let mag = self.magnitude();
-        Self {
+        if mag == T::zero() || !mag.is_finite() {
+            return None;
+        }
+        Some(Self {
             x: self.x / mag,
             y: self.y / mag,
             z: self.z / mag
-        }
+        })
+    }
+
+    /* True when every component is so small the vector could as well be zero */
+    pub fn near_zero(&self) -> bool {
+        let epsilon = T::from(1e-8).unwrap();
+        self.x.abs() < epsilon && self.y.abs() < epsilon && self.z.abs() < epsilon
     }
 
-    pub fn dot(&self, v: &Self) -> f64 {
+    pub fn dot(&self, v: &Self) -> T {
         (self.x * v.x) + (self.y * v.y) + (self.z * v.z)
     }
+
+    /* Vector perpendicular to both self and v following the right hand rule */
+    pub fn cross(&self, v: &Self) -> Self {
+        Self {
+            x: self.y * v.z - self.z * v.y,
+            y: self.z * v.x - self.x * v.z,
+            z: self.x * v.y - self.y * v.x
+        }
+    }
+
+    /* The smallest of each component */
+    pub fn min(&self, v: &Self) -> Self {
+        Self::new(self.x.min(v.x), self.y.min(v.y), self.z.min(v.z))
+    }
+
+    /* The largest of each component */
+    pub fn max(&self, v: &Self) -> Self {
+        Self::new(self.x.max(v.x), self.y.max(v.y), self.z.max(v.z))
+    }
+
+    /* Mirror the vector in a surface with unit normal n, like light bouncing off a mirror */
+    pub fn reflect(&self, n: &Normal3<T>) -> Self {
+        let n = n.to_vec();
+        *self - n * (T::from(2.0).unwrap() * self.dot(&n))
+    }
+
+    /* Bend a unit vector passing into a surface with unit normal n, which faces against the vector. The ratio is
+    the refractive index of the side the vector comes from over that of the other side, 1.0 / 1.5 going from air
+    into glass. None when the angle is too flat to pass and all light is reflected. */
+    pub fn refract(&self, n: &Normal3<T>, ratio: T) -> Option<Self> {
+        let n = n.to_vec();
+        let cos_theta = (-*self).dot(&n).min(T::one());
+        let sin_theta_squared = T::one() - cos_theta * cos_theta;
+        if ratio * ratio * sin_theta_squared > T::one() {
+            return None;
+        }
+        let perpendicular = (*self + n * cos_theta) * ratio;
+        let parallel = n * -(T::one() - perpendicular.dot(&perpendicular)).abs().sqrt();
+        Some(perpendicular + parallel)
+    }
 }
 
 
-// Implement Vec3 * f64
-impl ops::Mul<f64> for Vec3 {
-    type Output = Vec3;
+// Implement Vec3 * T
+impl<T: Float> ops::Mul<T> for Vec3<T> {
+    type Output = Vec3<T>;
 
-    fn mul(self, rhs: f64) -> Self::Output {
+    fn mul(self, rhs: T) -> Self::Output {
         Self {
             x: self.x * rhs,
             y: self.y * rhs,
@@ -48,19 +114,25 @@ impl ops::Mul<f64> for Vec3 {
     }
 }
 
-// Implement f64 * Vec3
-impl ops::Mul<Vec3> for f64 {
-    type Output = Vec3;
+// Implement f32 * Vec3 and f64 * Vec3, a generic T * Vec3 is not allowed
+macro_rules! scalar_times_vector {
+    ($($t:ty),*) => {$(
+        impl ops::Mul<Vec3<$t>> for $t {
+            type Output = Vec3<$t>;
 
-    fn mul(self, rhs: Vec3) -> Self::Output {
-        rhs * self
-    }
+            fn mul(self, rhs: Vec3<$t>) -> Self::Output {
+                rhs * self
+            }
+        }
+    )*}
 }
 
-impl ops::Sub<Vec3> for Vec3 {
-    type Output = Vec3;
+scalar_times_vector!(f32, f64);
+
+impl<T: Float> ops::Sub<Vec3<T>> for Vec3<T> {
+    type Output = Vec3<T>;
 
-    fn sub(self, rhs: Vec3) -> Self::Output {
+    fn sub(self, rhs: Vec3<T>) -> Self::Output {
         Self {
             x: self.x - rhs.x,
             y: self.y - rhs.y,
@@ -69,10 +141,10 @@ impl ops::Sub<Vec3> for Vec3 {
     }
 }
 
-impl ops::Add<Vec3> for Vec3 {
-    type Output = Vec3;
+impl<T: Float> ops::Add<Vec3<T>> for Vec3<T> {
+    type Output = Vec3<T>;
 
-    fn add(self, rhs: Vec3) -> Self::Output {
+    fn add(self, rhs: Vec3<T>) -> Self::Output {
         Self {
             x: self.x + rhs.x,
             y: self.y + rhs.y,
@@ -81,11 +153,60 @@ impl ops::Add<Vec3> for Vec3 {
     }
 }
 
-impl ops::Div<f64> for Vec3 {
-    type Output = Vec3;
+// Implement Vec3 * Vec3, multiplying each component
+impl<T: Float> ops::Mul<Vec3<T>> for Vec3<T> {
+    type Output = Vec3<T>;
+
+    fn mul(self, rhs: Vec3<T>) -> Self::Output {
+        Self {
+            x: self.x * rhs.x,
+            y: self.y * rhs.y,
+            z: self.z * rhs.z
+        }
+    }
+}
+
+impl<T: Float> ops::Neg for Vec3<T> {
+    type Output = Vec3<T>;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z
+        }
+    }
+}
 
-    fn div(self, rhs: f64) -> Self::Output {
-        (1.0 / rhs) * self
+impl<T: Float> ops::AddAssign<Vec3<T>> for Vec3<T> {
+    fn add_assign(&mut self, rhs: Vec3<T>) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T: Float> ops::SubAssign<Vec3<T>> for Vec3<T> {
+    fn sub_assign(&mut self, rhs: Vec3<T>) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T: Float> ops::MulAssign<T> for Vec3<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        *self = *self * rhs;
+    }
+}
+
+impl<T: Float> ops::DivAssign<T> for Vec3<T> {
+    fn div_assign(&mut self, rhs: T) {
+        *self = *self / rhs;
+    }
+}
+
+impl<T: Float> ops::Div<T> for Vec3<T> {
+    type Output = Vec3<T>;
+
+    fn div(self, rhs: T) -> Self::Output {
+        self * (T::one() / rhs)
     }
 }
 
@@ -94,6 +215,8 @@ mod tests {
 
     use super::*;
 
+    use crate::real::TOLERANCE;
+
     #[test]
     fn test_vector_dot_same() {
         let v1 = Vec3{x: 0.0, y: 0.0, z: 1.0};
@@ -117,4 +240,67 @@ mod tests {
 
         assert!(v1.dot(&v2) == 0.0);
     }
+
+    #[test]
+    fn test_vector_cross() {
+        let x = Vec3{x: 1.0, y: 0.0, z: 0.0};
+        let y = Vec3{x: 0.0, y: 1.0, z: 0.0};
+        let z = x.cross(&y);
+
+        assert!(z.x == 0.0 && z.y == 0.0 && z.z == 1.0);
+    }
+
+    #[test]
+    fn test_vector_normalise_zero() {
+        let zero = Vec3::new(0.0, 0.0, 0.0);
+
+        assert!(zero.try_normalise().is_none());
+        assert!(zero.normalise().near_zero());
+        assert!(Vec3::new(0.0, 3.0, 4.0).try_normalise().unwrap().magnitude() == 1.0);
+        assert!(!Vec3::new(0.0, 1e-6, 0.0).near_zero());
+    }
+
+    #[test]
+    fn test_vector_operators() {
+        let mut v = Vec3::new(1.0, 2.0, 3.0);
+        let w = Vec3::new(2.0, -1.0, 0.5);
+
+        let product = v * w;
+        assert!(product.x == 2.0 && product.y == -2.0 && product.z == 1.5);
+        assert!((v + -v).near_zero());
+        let (low, high) = (v.min(&w), v.max(&w));
+        assert!(low.x == 1.0 && low.y == -1.0 && low.z == 0.5);
+        assert!(high.x == 2.0 && high.y == 2.0 && high.z == 3.0);
+
+        v += w;
+        v -= Vec3::new(3.0, 1.0, 3.5);
+        v *= 2.0;
+        v /= 4.0;
+        assert!(v.near_zero());
+    }
+
+    #[test]
+    fn test_vector_reflect() {
+        let down = Vec3::new(1.0, -1.0, 0.0);
+        let up = Normal3::new(0.0, 1.0, 0.0);
+        let bounced = down.reflect(&up);
+
+        assert!(bounced.x == 1.0 && bounced.y == 1.0 && bounced.z == 0.0);
+    }
+
+    #[test]
+    fn test_vector_refract() {
+        let up = Normal3::new(0.0, 1.0, 0.0);
+        let straight = Vec3::new(0.0, -1.0, 0.0).refract(&up, 1.5).unwrap();
+        assert!((straight - Vec3::new(0.0, -1.0, 0.0)).near_zero());
+
+        // Snell's law, sin(in) * ratio = sin(out)
+        let slanted = Vec3::new(0.6, -0.8, 0.0);
+        let bent = slanted.refract(&up, 1.0 / 1.5).unwrap();
+        assert!((bent.magnitude() - 1.0).abs() < TOLERANCE);
+        assert!((bent.x - 0.6 / 1.5).abs() < TOLERANCE && bent.y < 0.0);
+
+        // Leaving glass at a flat angle the light cannot get out
+        assert!(Vec3::new(0.8, -0.6, 0.0).refract(&up, 1.5).is_none());
+    }
 }
@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::{read_config_from_file, Config};
+
+
+pub struct ConfigWatcher {
+    // Watches the config file, and the files it includes, by polling
+    // their modification times. Polling is cheap, but there is no need
+    // to hit the file system every frame so the files are only checked
+    // once per interval.
+    path: PathBuf,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_check: Instant,
+    interval: Duration
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn modified_times(files: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    files.iter().map(|path| (path.clone(), modified_time(path))).collect()
+}
+
+impl ConfigWatcher {
+    /* Watch the files a config was loaded from, the first one is reloaded when any of them changes. */
+    pub fn new(config: &Config, interval: Duration) -> Self {
+        Self {
+            path: config.files[0].clone(),
+            files: modified_times(&config.files),
+            last_check: Instant::now(),
+            interval
+        }
+    }
+
+    /* Reload the config if one of its files changed since the last time it was read.
+    Returns None when nothing changed, otherwise the result of parsing the new files. */
+    pub fn poll(&mut self) -> Option<Result<Config, Box<dyn Error>>> {
+        if self.last_check.elapsed() < self.interval {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        if self.files.iter().all(|(path, modified)| modified_time(path) == *modified) {
+            return None;
+        }
+        // Remember the new times even if parsing fails, so a broken file
+        // is reported once instead of every interval until it is fixed.
+        let paths: Vec<PathBuf> = self.files.iter().map(|(path, _)| path.clone()).collect();
+        self.files = modified_times(&paths);
+
+        let result = read_config_from_file(&self.path);
+        if let Ok(config) = &result {
+            // The includes may have changed as well.
+            self.files = modified_times(&config.files);
+        }
+        Some(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use super::*;
+
+    fn write_file(path: &Path, contents: &str, modified: SystemTime) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file.set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn test_poll_reloads_on_change() {
+        let path = std::env::temp_dir().join(format!("raytracer_watcher_test_{}.json", std::process::id()));
+        let start = SystemTime::now();
+        write_file(&path, r#"{ "version": "1.0", "spheres": [], "planes": [] }"#, start);
+
+        let config = read_config_from_file(&path).unwrap();
+        let mut watcher = ConfigWatcher::new(&config, Duration::ZERO);
+        assert!(watcher.poll().is_none());
+
+        write_file(&path, r#"{ "version": "1.0", "spheres": [ "#, start + Duration::from_secs(1));
+        assert!(matches!(watcher.poll(), Some(Err(_))));
+        assert!(watcher.poll().is_none());
+
+        write_file(&path, r#"{ "version": "1.0", "spheres": [], "planes": [] }"#, start + Duration::from_secs(2));
+        let config = watcher.poll().unwrap().unwrap();
+        assert_eq!(config.version, "1.0");
+
+        fs::remove_file(&path).unwrap();
+    }
+}